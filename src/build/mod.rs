@@ -1,4 +1,4 @@
-use crate::settings::toml::{Target, TargetType};
+use crate::settings::toml::{Builder, Target, TargetType};
 use crate::terminal::message::{Message, StdErr};
 use crate::terminal::styles;
 use crate::wranglerjs;
@@ -10,6 +10,10 @@ use std::process::Command;
 // Internal build logic, called by both `build` and `publish`
 // TODO: return a struct containing optional build info and construct output at command layer
 pub fn build_target(target: &Target) -> Result<String, failure::Error> {
+    if let Some(builder) = &target.build {
+        return run_custom_build(builder);
+    }
+
     let target_type = &target.target_type;
     match target_type {
         TargetType::JavaScript => {
@@ -48,6 +52,21 @@ pub fn build_target(target: &Target) -> Result<String, failure::Error> {
     }
 }
 
+// Runs the `[build]` command, if there is one, and checks that it produced the upload artifact.
+fn run_custom_build(builder: &Builder) -> Result<String, failure::Error> {
+    let msg = match builder.build_command() {
+        Some((command_str, command)) => {
+            StdErr::working(&format!("Running {}", command_str));
+            commands::run(command, command_str)?;
+            "Build succeeded"
+        }
+        None => "No build command specified, skipping build.",
+    };
+    builder.verify_artifact()?;
+
+    Ok(msg.to_string())
+}
+
 pub fn command(args: &[&str], binary_path: &PathBuf) -> Command {
     StdErr::working("Compiling your project to WebAssembly...");
 
@@ -0,0 +1,122 @@
+use reqwest::StatusCode;
+
+use crate::commands::kv;
+use crate::deploy::{DeployTarget, DeploymentSet, ScheduleTarget, ZonelessTarget};
+use crate::http;
+use crate::kv::namespace;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::sites;
+use crate::terminal::interactive;
+use crate::terminal::message::{Message, StdOut};
+
+// Deletes a Worker and everything wrangler set up for it: its routes, schedules,
+// workers.dev subdomain and Workers Sites namespaces.
+pub fn delete(
+    user: &GlobalUser,
+    target: &Target,
+    deployments: &DeploymentSet,
+    skip_confirmation: bool,
+) -> Result<(), failure::Error> {
+    if !skip_confirmation {
+        let confirmed = interactive::confirm(&format!(
+            "Are you sure you want to delete {}, the routes pointing at it, its schedules, its workers.dev subdomain and its Workers Sites namespaces? This cannot be undone.",
+            target.name
+        ))?;
+        if !confirmed {
+            StdOut::info(&format!("Not deleting {}", target.name));
+            return Ok(());
+        }
+    }
+
+    for deployment in deployments {
+        if let DeployTarget::Zoned(zoned) = deployment {
+            StdOut::working(&format!(
+                "Removing the routes in zone {} that point at {}",
+                zoned.zone_id, target.name
+            ));
+            for route in zoned.remove_routes(user, &target.name)? {
+                StdOut::success(&format!("Removed route {}", route.pattern));
+            }
+        }
+    }
+
+    if script_exists(user, target)? {
+        // schedules and the workers.dev subdomain may have been set up by an earlier
+        // version of the configuration, so they're removed whether configured or not
+        StdOut::working("Removing schedules");
+        ScheduleTarget::build(target.account_id.clone(), target.name.clone(), Vec::new())?
+            .clear(user)?;
+
+        StdOut::working("Removing from workers.dev");
+        ZonelessTarget {
+            account_id: target.account_id.clone(),
+            script_name: target.name.clone(),
+        }
+        .disable(user)?;
+
+        StdOut::working(&format!("Deleting script {}", target.name));
+        delete_script(user, target)?;
+    } else {
+        StdOut::info(&format!(
+            "There is no script named {}, skipping its schedules and workers.dev subdomain",
+            target.name
+        ));
+    }
+
+    for preview in &[false, true] {
+        if let Some(site_namespace) = sites::find_namespace(user, target, *preview)? {
+            StdOut::working(&format!(
+                "Deleting Workers Sites namespace {}",
+                site_namespace.id
+            ));
+            let client = http::cf_v4_client(user)?;
+            if let Err(e) = namespace::delete(client, target, &site_namespace.id) {
+                failure::bail!("{}", kv::format_error(e))
+            }
+        }
+    }
+
+    StdOut::success(&format!("Deleted {}", target.name));
+
+    Ok(())
+}
+
+fn script_addr(target: &Target) -> String {
+    format!(
+        "https://api.cloudflare.com/client/v4/accounts/{}/workers/scripts/{}",
+        target.account_id, target.name,
+    )
+}
+
+fn script_exists(user: &GlobalUser, target: &Target) -> Result<bool, failure::Error> {
+    let client = http::legacy_auth_client(user);
+
+    let res = client.get(&script_addr(target)).send()?;
+
+    match res.status() {
+        status if status.is_success() => Ok(true),
+        StatusCode::NOT_FOUND => Ok(false),
+        status => failure::bail!(
+            "Something went wrong! Status: {}, Details {}",
+            status,
+            res.text()?
+        ),
+    }
+}
+
+fn delete_script(user: &GlobalUser, target: &Target) -> Result<(), failure::Error> {
+    let client = http::legacy_auth_client(user);
+
+    let res = client.delete(&script_addr(target)).send()?;
+
+    if !res.status().is_success() {
+        failure::bail!(
+            "Something went wrong! Status: {}, Details {}",
+            res.status(),
+            res.text()?
+        )
+    }
+
+    Ok(())
+}
@@ -0,0 +1,49 @@
+use prettytable::{Cell, Row, Table};
+
+use crate::deploy::history;
+use crate::settings::toml::Target;
+use crate::terminal::message::{Message, StdOut};
+
+pub fn list(target: &Target) -> Result<(), failure::Error> {
+    let deployments = history::list(&target.name)?;
+    if deployments.is_empty() {
+        StdOut::info(&format!(
+            "No deployments of {} have been recorded yet, they are recorded every time you run `wrangler publish`",
+            target.name
+        ));
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("ID"),
+        Cell::new("Created"),
+        Cell::new("Script Hash"),
+        Cell::new("Routes"),
+        Cell::new("Schedules"),
+        Cell::new("Notes"),
+    ]));
+
+    // newest first, since that's what you're most likely looking for
+    for deployment in deployments.iter().rev() {
+        let mut routes = deployment.routes();
+        if deployment.workers_dev() {
+            routes.push("workers.dev".to_string());
+        }
+        let notes = match &deployment.rollback_of {
+            Some(id) => format!("rollback to {}", id),
+            None => String::new(),
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&deployment.id),
+            Cell::new(&deployment.created_on),
+            Cell::new(&deployment.script_hash),
+            Cell::new(&routes.join("\n")),
+            Cell::new(&deployment.crons().join("\n")),
+            Cell::new(&notes),
+        ]));
+    }
+    println!("{}", &table);
+
+    Ok(())
+}
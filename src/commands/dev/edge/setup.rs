@@ -33,7 +33,14 @@ pub(super) fn upload(
             StdOut::info("Uploading updated files...");
         }
 
-        bulk::put(target, user, &site_namespace.id, to_upload, &None)?;
+        bulk::put_files(
+            target,
+            user,
+            &site_namespace.id,
+            to_upload,
+            &None,
+            &mut |_| {},
+        )?;
         (to_delete, Some(asset_manifest), Some(site_namespace.id))
     } else {
         (Vec::new(), None, None)
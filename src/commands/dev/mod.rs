@@ -35,9 +35,16 @@ pub fn dev(
             .filter(|t| matches!(t, DeployTarget::Zoned(_) | DeployTarget::Zoneless(_)))
             .collect::<Vec<_>>();
 
+        // routes can be on several zones, so develop on the zone of the route `--host` matches
+        let host = server_config.host.to_string();
         let valid_target = valid_targets
             .iter()
-            .find(|&t| matches!(t, DeployTarget::Zoned(_)))
+            .find(|&t| !server_config.host.is_default() && matches_host(t, &host))
+            .or_else(|| {
+                valid_targets
+                    .iter()
+                    .find(|&t| matches!(t, DeployTarget::Zoned(_)))
+            })
             .or_else(|| {
                 valid_targets
                     .iter()
@@ -65,8 +72,10 @@ pub fn dev(
     }
 
     if let Some(user) = user {
-        if server_config.host.is_default() {
-            // Authenticated and no host provided, run on edge with user's zone
+        let host = server_config.host.to_string();
+        if server_config.host.is_default() || matches_host(&deploy_target, &host) {
+            // Authenticated and no host provided, or a host one of the routes handles,
+            // run on edge with that route's zone
             return edge::dev(
                 target,
                 user,
@@ -90,3 +99,10 @@ pub fn dev(
 
     gcs::dev(target, server_config, local_protocol, verbose)
 }
+
+fn matches_host(deploy_target: &DeployTarget, host: &str) -> bool {
+    match deploy_target {
+        DeployTarget::Zoned(zoned) => zoned.matches_host(host),
+        _ => false,
+    }
+}
@@ -4,13 +4,11 @@ use std::fs;
 use std::fs::metadata;
 use std::path::Path;
 
-use cloudflare::endpoints::workerskv::write_bulk::KeyValuePair;
-
 use indicatif::{ProgressBar, ProgressStyle};
 
 use crate::commands::kv;
-use crate::kv::bulk::delete;
 use crate::kv::bulk::BATCH_KEY_MAX;
+use crate::kv::bulk::{delete, KeyValuePair};
 use crate::settings::global_user::GlobalUser;
 use crate::settings::toml::Target;
 use crate::terminal::interactive;
@@ -96,7 +96,7 @@ pub fn get_namespace_id(target: &Target, binding: &str) -> Result<String, failur
 
 const KV_ASCII_SET: &AsciiSet = &CONTROLS.add(b'/');
 
-fn url_encode_key(key: &str) -> String {
+pub fn url_encode_key(key: &str) -> String {
     utf8_percent_encode(key, KV_ASCII_SET).to_string()
 }
 
@@ -125,6 +125,7 @@ mod tests {
             site: None,
             vars: None,
             text_blobs: None,
+            build: None,
         };
         assert!(kv::get_namespace_id(&target_with_dup_kv_bindings, "").is_err());
     }
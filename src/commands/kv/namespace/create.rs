@@ -1,12 +1,15 @@
+use std::path::Path;
+
 use regex::Regex;
 
 use crate::commands::kv;
 use crate::http;
 use crate::kv::namespace::create;
 use crate::settings::global_user::GlobalUser;
-use crate::settings::toml::{ConfigKvNamespace, KvNamespace, Manifest};
+use crate::settings::toml::{ConfigKvNamespace, KvNamespace, Manifest, ManifestEditor};
 use crate::terminal::message::{Message, StdOut};
 pub fn run(
+    config_path: &Path,
     manifest: &Manifest,
     is_preview: bool,
     env: Option<&str>,
@@ -17,10 +20,7 @@ pub fn run(
     let worker_name = manifest.worker_name(env);
     validate_binding(binding)?;
 
-    let mut title = format!("{}-{}", worker_name, binding);
-    if is_preview {
-        title.push_str("_preview");
-    }
+    let title = namespace_title(&worker_name, binding, is_preview);
     let msg = format!("Creating namespace with title \"{}\"", title);
     StdOut::working(&msg);
 
@@ -29,20 +29,36 @@ pub fn run(
 
     match result {
         Ok(success) => {
-            let namespace = success.result;
+            let namespace = KvNamespace {
+                binding: binding.to_string(),
+                id: success.result.id,
+            };
             StdOut::success("Success!");
-            println!(
-                "{}",
-                toml_modification_instructions(
-                    KvNamespace {
-                        binding: binding.to_string(),
-                        id: namespace.id,
-                    },
-                    manifest.kv_namespaces.as_ref(),
-                    env,
-                    is_preview,
-                )
-            );
+            match add_to_config(config_path, &namespace, env, is_preview) {
+                Ok(()) => StdOut::success(&format!(
+                    "Added {} to the kv_namespaces in {}{}",
+                    binding,
+                    config_path.display(),
+                    env.map(|env| format!(" under [env.{}]", env))
+                        .unwrap_or_default()
+                )),
+                Err(e) => {
+                    StdOut::warn(&format!(
+                        "Could not update {}: {}",
+                        config_path.display(),
+                        e
+                    ));
+                    println!(
+                        "{}",
+                        toml_modification_instructions(
+                            namespace,
+                            manifest.kv_namespaces.as_ref(),
+                            env,
+                            is_preview,
+                        )
+                    );
+                }
+            }
         }
         Err(e) => print!("{}", kv::format_error(e)),
     }
@@ -50,6 +66,15 @@ pub fn run(
     Ok(())
 }
 
+// Namespaces are titled after the worker and binding they are created for.
+pub fn namespace_title(worker_name: &str, binding: &str, is_preview: bool) -> String {
+    let mut title = format!("{}-{}", worker_name, binding);
+    if is_preview {
+        title.push_str("_preview");
+    }
+    title
+}
+
 fn validate_binding(binding: &str) -> Result<(), failure::Error> {
     let re = Regex::new(r"^[a-zA-Z_][a-zA-Z0-9_]*$").unwrap();
     if !re.is_match(binding) {
@@ -60,6 +85,17 @@ fn validate_binding(binding: &str) -> Result<(), failure::Error> {
     Ok(())
 }
 
+fn add_to_config(
+    config_path: &Path,
+    namespace: &KvNamespace,
+    env: Option<&str>,
+    is_preview: bool,
+) -> Result<(), failure::Error> {
+    let mut editor = ManifestEditor::open(config_path)?;
+    editor.upsert_kv_namespace(env, &namespace.binding, &namespace.id, is_preview)?;
+    editor.save()
+}
+
 fn toml_modification_instructions(
     new_namespace: KvNamespace,
     all_namespaces: Option<&Vec<ConfigKvNamespace>>,
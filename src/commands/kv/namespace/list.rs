@@ -10,7 +10,7 @@ pub fn run(target: &Target, user: &GlobalUser) -> Result<(), failure::Error> {
     kv::validate_target(target)?;
 
     let client = http::cf_v4_client(user)?;
-    let result = list(&client, target);
+    let result = list(&client, &target.account_id);
     match result {
         Ok(namespaces) => {
             println!("{}", serde_json::to_string(&namespaces)?);
@@ -2,6 +2,7 @@ mod create;
 mod delete;
 mod list;
 
+pub use create::namespace_title;
 pub use create::run as create;
 pub use delete::run as delete;
 pub use list::run as list;
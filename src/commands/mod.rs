@@ -2,26 +2,38 @@ use std::process::Command;
 
 pub mod build;
 pub mod config;
+pub mod delete;
+pub mod deployments;
 pub mod dev;
 pub mod generate;
 pub mod init;
 pub mod kv;
 pub mod login;
 mod preview;
+pub mod preview_branch;
+pub mod promote;
+pub mod provision;
 pub mod publish;
+pub mod rollback;
 pub mod route;
 pub mod secret;
+pub mod site;
 pub mod subdomain;
 pub mod tail;
+pub mod triggers;
 pub mod whoami;
 
 pub use self::config::global_config;
 pub use self::preview::run as preview;
 pub use build::build;
+pub use delete::delete;
 pub use dev::dev;
 pub use generate::generate;
 pub use init::init;
-pub use publish::publish;
+pub use promote::promote;
+pub use provision::provision;
+pub use publish::{publish, PublishOpts};
+pub use rollback::rollback;
 pub use secret::{create_secret, delete_secret, list_secrets};
 pub use subdomain::get_subdomain;
 pub use subdomain::set_subdomain;
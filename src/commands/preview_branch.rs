@@ -0,0 +1,292 @@
+use std::fs;
+use std::hash::Hasher;
+use std::path::PathBuf;
+
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::commands::{self, validate_worker_name, PublishOpts};
+use crate::deploy::{history, DeployTarget, ZonelessTarget};
+use crate::settings::get_project_state_dir;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::{Manifest, Target};
+use crate::terminal::message::{Message, Output, StdErr, StdOut};
+
+const PREVIEW_BRANCHES_FILE_NAME: &str = "preview_branches.json";
+// the longest name a script can have
+const MAX_SCRIPT_NAME_LENGTH: usize = 63;
+// how many hex digits of the hash of the branch end a script name that had to be shortened
+const BRANCH_HASH_LENGTH: usize = 8;
+
+/// A script published with `wrangler publish --preview-branch`.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct PreviewBranch {
+    pub branch: String,
+    pub script_name: String,
+    pub account_id: String,
+    pub env: Option<String>,
+    // the last time the branch was published
+    pub published_on: String,
+}
+
+// Publishes the worker of `env` as a script of its own for `branch`, on workers.dev only and
+// bound to the preview ids of its KV namespaces.
+pub fn publish(
+    user: &GlobalUser,
+    manifest: &Manifest,
+    env: Option<&str>,
+    branch: &str,
+    out: Output,
+    opts: PublishOpts,
+) -> Result<(), failure::Error> {
+    let script_name = script_name(&manifest.worker_name(env), branch)?;
+    let mut target = manifest.get_target(env, true)?;
+    target.name = script_name.clone();
+
+    let deployments = vec![DeployTarget::Zoneless(ZonelessTarget {
+        account_id: target.account_id.clone(),
+        script_name: script_name.clone(),
+    })];
+
+    let dry_run = opts.dry_run;
+    commands::publish(user, &mut target, deployments, out, opts)?;
+    if dry_run {
+        return Ok(());
+    }
+
+    let preview_branch = PreviewBranch {
+        branch: branch.to_string(),
+        script_name,
+        account_id: target.account_id,
+        env: env.map(str::to_string),
+        published_on: Utc::now().to_rfc3339(),
+    };
+    // like the deployment history, failing to record the branch shouldn't fail the publish
+    if let Err(e) = record(preview_branch) {
+        StdErr::warn(&format!(
+            "Could not record this preview branch, `wrangler preview-branch cleanup --older-than` won't find it: {}",
+            e
+        ));
+    }
+
+    Ok(())
+}
+
+// Deletes the scripts and Workers Sites namespaces of the preview branch named `branch`, or of
+// every preview branch that wasn't published within `older_than`.
+pub fn cleanup(
+    user: &GlobalUser,
+    manifest: &Manifest,
+    env: Option<&str>,
+    branch: Option<&str>,
+    older_than: Option<Duration>,
+) -> Result<(), failure::Error> {
+    let mut preview_branches = load()?;
+
+    let expired: Vec<PreviewBranch> = match (branch, older_than) {
+        (Some(branch), _) => {
+            let script_name = script_name(&manifest.worker_name(env), branch)?;
+            match preview_branches
+                .iter()
+                .find(|preview_branch| preview_branch.script_name == script_name)
+            {
+                Some(preview_branch) => vec![preview_branch.clone()],
+                // the branch may have been published from somewhere else, e.g. CI
+                None => vec![PreviewBranch {
+                    branch: branch.to_string(),
+                    script_name,
+                    account_id: manifest.get_target(env, true)?.account_id,
+                    env: env.map(str::to_string),
+                    published_on: String::new(),
+                }],
+            }
+        }
+        (None, Some(older_than)) => {
+            let cutoff = Utc::now() - older_than;
+            let mut expired = Vec::new();
+            for preview_branch in &preview_branches {
+                let published_on = DateTime::parse_from_rfc3339(&preview_branch.published_on)?;
+                if published_on.with_timezone(&Utc) < cutoff {
+                    expired.push(preview_branch.clone());
+                }
+            }
+            expired
+        }
+        (None, None) => failure::bail!("Name the preview branch to clean up, or pass --older-than"),
+    };
+
+    if expired.is_empty() {
+        StdOut::info("There are no preview branches to clean up");
+        return Ok(());
+    }
+
+    for preview_branch in expired {
+        StdOut::working(&format!(
+            "Cleaning up preview branch {} ({})",
+            preview_branch.branch, preview_branch.script_name
+        ));
+        let target = Target {
+            account_id: preview_branch.account_id.clone(),
+            name: preview_branch.script_name.clone(),
+            ..Target::default()
+        };
+        commands::delete::delete(user, &target, &Vec::new(), true)?;
+        history::forget(&preview_branch.script_name)?;
+
+        // saved after every branch, so the ones that were deleted stay forgotten if a later one fails
+        preview_branches.retain(|recorded| recorded.script_name != preview_branch.script_name);
+        save(&preview_branches)?;
+    }
+
+    Ok(())
+}
+
+// Parses ages such as `30m`, `12h`, `7d` or `2w`.
+pub fn parse_age(age: &str) -> Result<Duration, failure::Error> {
+    let unit_start = age.find(|c: char| !c.is_ascii_digit()).unwrap_or(age.len());
+    let amount = match age[..unit_start].parse::<i64>() {
+        Ok(amount) => amount,
+        Err(_) => failure::bail!("\"{}\" is not an age, use e.g. 12h, 7d or 2w", age),
+    };
+
+    match &age[unit_start..] {
+        "m" => Ok(Duration::minutes(amount)),
+        "h" => Ok(Duration::hours(amount)),
+        "d" => Ok(Duration::days(amount)),
+        "w" => Ok(Duration::weeks(amount)),
+        _ => failure::bail!("\"{}\" is not an age, use e.g. 12h, 7d or 2w", age),
+    }
+}
+
+// The script name of `branch`: the worker name followed by the branch, with everything a
+// script name can't contain replaced by dashes. Names that are too long are shortened and end
+// in a hash of the whole branch instead, so that branches with the same start don't share a
+// script.
+fn script_name(worker_name: &str, branch: &str) -> Result<String, failure::Error> {
+    let mut slug = String::new();
+    for c in branch.to_lowercase().chars() {
+        if c.is_ascii_alphanumeric() || c == '_' {
+            slug.push(c);
+        } else if !slug.ends_with('-') {
+            slug.push('-');
+        }
+    }
+    let slug = slug.trim_matches('-');
+    if slug.is_empty() {
+        failure::bail!(
+            "The preview branch \"{}\" needs at least one letter or number",
+            branch
+        )
+    }
+
+    let mut script_name = format!("{}-{}", worker_name, slug);
+    if script_name.len() > MAX_SCRIPT_NAME_LENGTH {
+        let mut hasher = XxHash64::default();
+        hasher.write(branch.as_bytes());
+        let hash = format!("{:016x}", hasher.finish());
+
+        script_name.truncate(MAX_SCRIPT_NAME_LENGTH - BRANCH_HASH_LENGTH - 1);
+        script_name = format!(
+            "{}-{}",
+            script_name.trim_end_matches('-'),
+            &hash[..BRANCH_HASH_LENGTH]
+        );
+    }
+    // cleaning up a preview branch deletes its script, which must never be the worker itself
+    if script_name == worker_name {
+        failure::bail!(
+            "The preview branch \"{}\" would be published as {} itself",
+            branch,
+            worker_name
+        )
+    }
+    validate_worker_name(&script_name)?;
+
+    Ok(script_name)
+}
+
+fn record(preview_branch: PreviewBranch) -> Result<(), failure::Error> {
+    let mut preview_branches = load()?;
+    preview_branches.retain(|recorded| recorded.script_name != preview_branch.script_name);
+    preview_branches.push(preview_branch);
+    save(&preview_branches)
+}
+
+fn load() -> Result<Vec<PreviewBranch>, failure::Error> {
+    let path = preview_branches_path()?;
+    if !path.is_file() {
+        return Ok(Vec::new());
+    }
+
+    Ok(serde_json::from_str(&fs::read_to_string(path)?)?)
+}
+
+fn save(preview_branches: &[PreviewBranch]) -> Result<(), failure::Error> {
+    let path = preview_branches_path()?;
+    if let Some(state_dir) = path.parent() {
+        fs::create_dir_all(state_dir)?;
+    }
+    fs::write(path, serde_json::to_string_pretty(preview_branches)?)?;
+
+    Ok(())
+}
+
+fn preview_branches_path() -> Result<PathBuf, failure::Error> {
+    Ok(get_project_state_dir()?.join(PREVIEW_BRANCHES_FILE_NAME))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_names_scripts_after_the_branch() {
+        assert_eq!(
+            script_name("worker", "feature/Add-Login").unwrap(),
+            "worker-feature-add-login"
+        );
+        assert_eq!(
+            script_name("worker-staging", "fix__cache..headers").unwrap(),
+            "worker-staging-fix__cache-headers"
+        );
+        assert!(script_name("worker", "///").is_err());
+
+        let long_branch = "a-very-long-branch-name-that-goes-on-and-on-for-ever-and-ever";
+        let long_name = script_name("worker", long_branch).unwrap();
+        assert_eq!(long_name.len(), MAX_SCRIPT_NAME_LENGTH);
+        assert!(long_name.starts_with("worker-a-very-long-branch"));
+    }
+
+    #[test]
+    fn it_tells_shortened_branch_names_apart() {
+        let first = script_name(
+            "worker",
+            "dependabot/npm_and_yarn/packages/frontend/webpack-dev-server-3.11.0",
+        )
+        .unwrap();
+        let second = script_name(
+            "worker",
+            "dependabot/npm_and_yarn/packages/frontend/webpack-dev-server-3.11.2",
+        )
+        .unwrap();
+        assert_ne!(first, second);
+        assert!(first.len() <= MAX_SCRIPT_NAME_LENGTH);
+        assert!(second.len() <= MAX_SCRIPT_NAME_LENGTH);
+
+        let long_worker_name = "w".repeat(MAX_SCRIPT_NAME_LENGTH);
+        let preview_name = script_name(&long_worker_name, "feature").unwrap();
+        assert_ne!(preview_name, long_worker_name);
+        assert!(preview_name.len() <= MAX_SCRIPT_NAME_LENGTH);
+    }
+
+    #[test]
+    fn it_parses_ages() {
+        assert_eq!(parse_age("30m").unwrap(), Duration::minutes(30));
+        assert_eq!(parse_age("7d").unwrap(), Duration::days(7));
+        assert_eq!(parse_age("2w").unwrap(), Duration::weeks(2));
+        assert!(parse_age("7").is_err());
+        assert!(parse_age("d").is_err());
+        assert!(parse_age("7y").is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::collections::HashSet;
+
+use crate::commands::kv;
+use crate::deploy::{self, history, DeployOpts, DeploymentSet};
+use crate::http::{self, Feature};
+use crate::kv::bulk::{self, KeyFile};
+use crate::kv::key::KeyList;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::sites::{self, AssetManifest};
+use crate::terminal::message::{Message, StdErr};
+use crate::upload;
+
+// Publishes the latest deployment of `source` as `target` without building anything:
+// the script, modules, wasm and asset manifest are the ones that were uploaded for
+// `source`, bound to the kv_namespaces, vars and text_blobs of `target`.
+pub fn promote(
+    user: &GlobalUser,
+    source_target: &Target,
+    target: &mut Target,
+    deployments: &DeploymentSet,
+) -> Result<(), failure::Error> {
+    let from_script = source_target.name.as_str();
+    if from_script == target.name {
+        failure::bail!(
+            "{} can't be promoted to itself, choose environments with different script names",
+            from_script
+        )
+    }
+
+    let source = match history::list(from_script)?.pop() {
+        Some(source) => source,
+        None => failure::bail!(
+            "There is no recorded deployment of {} to promote. Publish it from this project first.",
+            from_script
+        ),
+    };
+    StdErr::working(&format!(
+        "Promoting deployment {} of {} from {} to {}",
+        source.id, from_script, source.created_on, target.name
+    ));
+
+    let site_namespace_id = match &source.asset_manifest {
+        Some(asset_manifest) => Some(upload_site_assets(
+            user,
+            source_target,
+            target,
+            asset_manifest,
+        )?),
+        None => None,
+    };
+    let upload_client = if site_namespace_id.is_some() {
+        http::featured_legacy_auth_client(user, Feature::Sites)
+    } else {
+        http::legacy_auth_client(user)
+    };
+
+    let assets = upload::form::rebind(source.project_assets()?, target)?;
+    upload::script(&upload_client, target, &assets)?;
+
+    let results = deploy::worker(user, &target.name, deployments, &DeployOpts::default())?;
+    // the promoted files are live in `target` now, so they are kept like a publish's
+    if let (Some(asset_manifest), Some(namespace_id)) = (&source.asset_manifest, &site_namespace_id)
+    {
+        sites::record_generation(target, user, namespace_id, asset_manifest);
+    }
+    let removed_schedules =
+        deploy::clear_stale_schedules(user, &target.account_id, &target.name, deployments);
+
+    let promoted = history::record(
+        &target.name,
+        &assets,
+        source.asset_manifest.clone(),
+        deployments,
+        None,
+    )?;
+
+    let mut msg = format!(
+        "Promoted deployment {} of {} to {}, recorded as deployment {}",
+        source.id, from_script, target.name, promoted.id
+    );
+    if !results.urls.is_empty() {
+        msg.push_str(&format!("\n {}", results.urls.join("\n ")));
+    }
+    if !results.schedules.is_empty() {
+        msg.push_str(&format!(
+            "\nwith this schedule\n {}",
+            results.schedules.join("\n ")
+        ));
+    }
+    if results.workers_dev_disabled {
+        msg.push_str("\nand disabled its workers.dev subdomain");
+    }
+    if !removed_schedules.is_empty() {
+        msg.push_str(&format!(
+            "\nand removed the schedule\n {}",
+            removed_schedules.join("\n ")
+        ));
+    }
+    StdErr::success(&msg);
+
+    Ok(())
+}
+
+// Makes sure every file in the asset manifest of the promoted deployment is in the site
+// namespace of `target`, and returns the id of that namespace. Keys end in a hash of the file
+// contents, so a local file with the same key is exactly the file that was published.
+fn upload_site_assets(
+    user: &GlobalUser,
+    source_target: &Target,
+    target: &mut Target,
+    asset_manifest: &AssetManifest,
+) -> Result<String, failure::Error> {
+    let from_script = &source_target.name;
+    if target.site.is_none() {
+        failure::bail!(
+            "{} was published with a Workers Site, so {} needs a [site] in your configuration file",
+            from_script,
+            target.name
+        )
+    }
+    // the keys depend on the headers, precompression and chunking of the [site] they were
+    // published with, so the files are hashed with the source's
+    let bucket = match &source_target.site {
+        Some(site) => site.bucket.clone(),
+        None => failure::bail!(
+            "{} was published with a Workers Site, but its [site] is no longer in your configuration file",
+            from_script
+        ),
+    };
+    let site_namespace = sites::add_namespace(user, target, false)?;
+
+    let client = http::cf_v4_client(user)?;
+    let mut remote_keys: HashSet<String> = HashSet::new();
+    for remote_key in KeyList::new(target, client, &site_namespace.id, None)? {
+        match remote_key {
+            Ok(remote_key) => {
+                remote_keys.insert(remote_key.name);
+            }
+            Err(e) => failure::bail!(kv::format_error(e)),
+        }
+    }
+
+    let missing: HashSet<&String> = asset_manifest
+        .kv_keys()
+        .filter(|key| !remote_keys.contains(*key))
+        .collect();
+    if missing.is_empty() {
+        return Ok(site_namespace.id);
+    }
+
+    let (files, _) = sites::directory_files(source_target, &bucket)?;
+    let to_upload: Vec<KeyFile> = files
+        .into_iter()
+        .filter(|file| missing.contains(&file.key))
+        .collect();
+    if to_upload.len() < missing.len() {
+        failure::bail!(
+            "{} site file(s) published with {} have changed in {} since. Publish {} again before promoting it.",
+            missing.len() - to_upload.len(),
+            from_script,
+            bucket.display(),
+            from_script
+        )
+    }
+
+    StdErr::working(&format!("Uploading {} site file(s)", to_upload.len()));
+    bulk::put_files(
+        target,
+        user,
+        &site_namespace.id,
+        to_upload,
+        &None,
+        &mut |_| {},
+    )?;
+
+    Ok(site_namespace.id)
+}
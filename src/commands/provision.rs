@@ -0,0 +1,125 @@
+use std::path::Path;
+
+use prettytable::{Cell, Row, Table};
+
+use crate::commands::kv::namespace::namespace_title;
+use crate::kv::namespace::{upsert, UpsertedNamespace};
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::{Manifest, ManifestEditor};
+use crate::terminal::message::{Message, StdOut};
+
+// A KV namespace id that `provision` filled in.
+struct ProvisionedNamespace {
+    env: Option<String>,
+    binding: String,
+    is_preview: bool,
+    title: String,
+    id: String,
+    created: bool,
+}
+
+// Creates (or finds) a KV namespace for every id and preview_id missing from the
+// kv_namespaces of each environment, and writes the ids into the configuration file.
+pub fn provision(
+    config_path: &Path,
+    manifest: &Manifest,
+    user: &GlobalUser,
+) -> Result<(), failure::Error> {
+    let mut environments = vec![None];
+    if let Some(env) = &manifest.env {
+        let mut names: Vec<&str> = env.keys().map(String::as_str).collect();
+        names.sort_unstable();
+        environments.extend(names.into_iter().map(Some));
+    }
+
+    let mut editor = ManifestEditor::open(config_path)?;
+    let mut provisioned = Vec::new();
+
+    for env in environments {
+        let mut missing = Vec::new();
+        for namespace in manifest.kv_namespace_configs(env)? {
+            if namespace.id.is_none() {
+                missing.push((namespace.binding.clone(), false));
+            }
+            if namespace.preview_id.is_none() {
+                missing.push((namespace.binding, true));
+            }
+        }
+        if missing.is_empty() {
+            continue;
+        }
+
+        let account_id = manifest.get_account_id(env)?;
+        let worker_name = manifest.worker_name(env);
+
+        for (binding, is_preview) in missing {
+            let title = namespace_title(&worker_name, &binding, is_preview);
+            StdOut::working(&format!("Provisioning namespace \"{}\"", title));
+
+            let (id, created) = match upsert(&account_id, user, title.clone())? {
+                UpsertedNamespace::Created(namespace) => (namespace.id, true),
+                UpsertedNamespace::Reused(namespace) => (namespace.id, false),
+            };
+
+            // save after every namespace, so nothing is lost if a later one fails
+            editor.upsert_kv_namespace(env, &binding, &id, is_preview)?;
+            editor.save()?;
+
+            provisioned.push(ProvisionedNamespace {
+                env: env.map(str::to_string),
+                binding,
+                is_preview,
+                title,
+                id,
+                created,
+            });
+        }
+    }
+
+    if provisioned.is_empty() {
+        StdOut::success(&format!(
+            "Every KV namespace in {} already has an id and a preview_id",
+            config_path.display()
+        ));
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Environment"),
+        Cell::new("Binding"),
+        Cell::new("Field"),
+        Cell::new("Namespace"),
+        Cell::new("ID"),
+        Cell::new("Status"),
+    ]));
+    for namespace in &provisioned {
+        table.add_row(Row::new(vec![
+            Cell::new(namespace.env.as_deref().unwrap_or("(top level)")),
+            Cell::new(&namespace.binding),
+            Cell::new(if namespace.is_preview {
+                "preview_id"
+            } else {
+                "id"
+            }),
+            Cell::new(&namespace.title),
+            Cell::new(&namespace.id),
+            Cell::new(if namespace.created {
+                "created"
+            } else {
+                "reused"
+            }),
+        ]));
+    }
+    println!("{}", &table);
+
+    let created = provisioned.iter().filter(|n| n.created).count();
+    StdOut::success(&format!(
+        "Created {} and reused {} namespaces, and added their ids to {}",
+        created,
+        provisioned.len() - created,
+        config_path.display()
+    ));
+
+    Ok(())
+}
@@ -0,0 +1,407 @@
+use std::path::Path;
+
+use serde::Serialize;
+
+use crate::build::build_target;
+use crate::commands::subdomain::Subdomain;
+use crate::deploy::{self, DeployTarget, RouteUploadResult, ScheduleTarget};
+use crate::settings::binding::Binding;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::{KvNamespace, Target};
+use crate::sites::{self, AssetManifest};
+use crate::terminal::message::{Message, Output, StdErr, StdOut};
+use crate::upload::{self, form::ProjectAssets};
+
+use super::{validate_bucket_location, PublishOpts};
+
+#[derive(Serialize, Default)]
+pub struct DryRunOutput {
+    pub name: String,
+    pub bindings: Vec<Binding>,
+    // every module uploaded alongside the main module, for module workers
+    pub modules: Vec<String>,
+    pub routes: Vec<RouteUploadResult>,
+    pub urls: Vec<String>,
+    pub schedules: Vec<String>,
+    pub workers_dev_disabled: bool,
+    pub removed_schedules: Vec<String>,
+    pub assets: Option<AssetChanges>,
+}
+
+#[derive(Serialize)]
+pub struct AssetChanges {
+    pub to_upload: usize,
+    pub to_delete: usize,
+}
+
+// Builds everything `wrangler publish` would send, and reports what would change,
+// without making a single mutating API call.
+pub fn dry_run(
+    user: &GlobalUser,
+    target: &mut Target,
+    deployments: &[DeployTarget],
+    opts: &PublishOpts,
+    out: Output,
+) -> Result<(), failure::Error> {
+    let msg = build_target(&target)?;
+    StdErr::success(&msg);
+
+    let (asset_manifest, asset_changes) = if let Some(site_config) = target.site.clone() {
+        let path = &site_config.bucket;
+        validate_bucket_location(path)?;
+
+        let (to_upload, to_delete, asset_manifest) =
+            match sites::find_namespace(user, target, false)? {
+                Some(site_namespace) => {
+                    target.add_kv_namespace(site_namespace.clone());
+                    let (to_upload, to_delete, asset_manifest) =
+                        sites::sync(target, user, &site_namespace.id, path)?;
+                    let to_delete = match site_config.retain_versions {
+                        Some(retain_versions) => {
+                            sites::find_garbage(
+                                target,
+                                user,
+                                &site_namespace.id,
+                                retain_versions,
+                                Some(&asset_manifest),
+                            )?
+                            .keys
+                        }
+                        None => to_delete,
+                    };
+                    (to_upload.len(), to_delete.len(), asset_manifest)
+                }
+                None => {
+                    // the namespace would be created on publish, so every file is new
+                    target.add_kv_namespace(KvNamespace {
+                        binding: "__STATIC_CONTENT".to_string(),
+                        id: "<created on publish>".to_string(),
+                    });
+                    let (files, asset_manifest) = sites::directory_files(target, path)?;
+                    (files.len(), 0, asset_manifest)
+                }
+            };
+
+        (
+            Some(asset_manifest),
+            Some(AssetChanges {
+                to_upload,
+                to_delete,
+            }),
+        )
+    } else {
+        (None, None)
+    };
+
+    let assets = upload_assets(target, asset_manifest, opts.outdir.as_deref())?;
+
+    let mut planned = Planned {
+        assets: asset_changes,
+        ..Planned::default()
+    };
+    for deployment in deployments {
+        match deployment {
+            DeployTarget::Zoned(zoned) => planned.routes.extend(zoned.plan(user, &target.name, &opts.deploy_opts())?),
+            DeployTarget::Zoneless(zoneless) => {
+                match Subdomain::get(&zoneless.account_id, user)? {
+                    Some(subdomain) => planned.urls.push(format!(
+                        "https://{}.{}.workers.dev",
+                        zoneless.script_name, subdomain
+                    )),
+                    None => StdErr::warn("Before publishing to workers.dev, you must register a subdomain. Please choose a name for your subdomain and run `wrangler subdomain <name>`."),
+                }
+            }
+            DeployTarget::WorkersDevDisabled(_) | DeployTarget::Schedule(_) => {}
+        }
+    }
+
+    let schedules =
+        ScheduleTarget::build(target.account_id.clone(), target.name.clone(), Vec::new())?;
+    planned.removed_schedules = deploy::stale_schedules(user, &schedules, deployments)?;
+
+    let output = report(target, &assets, deployments, planned);
+    match out {
+        Output::Json => StdOut::as_json(&output),
+        Output::PlainText => {
+            StdErr::info(&render(&output));
+            StdErr::success("Dry run complete, nothing was published");
+        }
+    }
+
+    Ok(())
+}
+
+// What publishing would change in the account, as found without changing any of it.
+#[derive(Default)]
+struct Planned {
+    routes: Vec<RouteUploadResult>,
+    urls: Vec<String>,
+    removed_schedules: Vec<String>,
+    assets: Option<AssetChanges>,
+}
+
+// Collects the assets that publishing would upload, and writes the parts of their upload
+// form to `outdir` when one is given.
+fn upload_assets(
+    target: &Target,
+    asset_manifest: Option<AssetManifest>,
+    outdir: Option<&Path>,
+) -> Result<ProjectAssets, failure::Error> {
+    let assets = upload::form::project_assets(target, asset_manifest)?;
+
+    if let Some(outdir) = outdir {
+        upload::form::write_parts(&assets, outdir)?;
+        StdErr::info(&format!("Wrote upload form parts to {}", outdir.display()));
+    }
+
+    Ok(assets)
+}
+
+fn report(
+    target: &Target,
+    assets: &ProjectAssets,
+    deployments: &[DeployTarget],
+    planned: Planned,
+) -> DryRunOutput {
+    let mut output = DryRunOutput {
+        name: target.name.clone(),
+        bindings: assets.bindings(),
+        modules: assets.modules.iter().map(|module| module.name()).collect(),
+        routes: planned.routes,
+        urls: planned.urls,
+        removed_schedules: planned.removed_schedules,
+        assets: planned.assets,
+        ..DryRunOutput::default()
+    };
+
+    for deployment in deployments {
+        match deployment {
+            DeployTarget::WorkersDevDisabled(_) => output.workers_dev_disabled = true,
+            DeployTarget::Schedule(schedule) => output.schedules.extend(schedule.crons.clone()),
+            DeployTarget::Zoned(_) | DeployTarget::Zoneless(_) => {}
+        }
+    }
+
+    output
+}
+
+fn render(output: &DryRunOutput) -> String {
+    let mut lines = vec![format!("Dry run of publishing \"{}\"", output.name)];
+
+    lines.push("bindings:".to_string());
+    for binding in &output.bindings {
+        lines.push(format!(" {}", display_binding(binding)));
+    }
+
+    if !output.modules.is_empty() {
+        lines.push("modules:".to_string());
+        for module in &output.modules {
+            lines.push(format!(" {}", module));
+        }
+    }
+
+    if !output.routes.is_empty() {
+        lines.push("routes:".to_string());
+        for route in &output.routes {
+            lines.push(format!(" {}", display_route(route)));
+        }
+    }
+
+    if !output.urls.is_empty() {
+        lines.push("workers.dev:".to_string());
+        for url in &output.urls {
+            lines.push(format!(" {}", url));
+        }
+    }
+
+    if output.workers_dev_disabled {
+        lines.push("workers.dev:\n would be disabled".to_string());
+    }
+
+    if !output.schedules.is_empty() {
+        lines.push("schedules:".to_string());
+        for schedule in &output.schedules {
+            lines.push(format!(" {}", schedule));
+        }
+    }
+
+    if !output.removed_schedules.is_empty() {
+        lines.push("schedules to remove:".to_string());
+        for schedule in &output.removed_schedules {
+            lines.push(format!(" {}", schedule));
+        }
+    }
+
+    if let Some(assets) = &output.assets {
+        lines.push(format!(
+            "site assets:\n {} to upload\n {} to delete",
+            assets.to_upload, assets.to_delete
+        ));
+    }
+
+    lines.join("\n")
+}
+
+fn display_binding(binding: &Binding) -> String {
+    match binding {
+        Binding::WasmModule { name, part } => format!("{} => wasm module \"{}\"", name, part),
+        Binding::KvNamespace { name, namespace_id } => {
+            format!("{} => kv namespace {}", name, namespace_id)
+        }
+        Binding::TextBlob { name, part } => format!("{} => text blob \"{}\"", name, part),
+        Binding::PlainText { name, .. } => format!("{} => plain text", name),
+    }
+}
+
+fn display_route(result: &RouteUploadResult) -> String {
+    match result {
+        RouteUploadResult::New(route) => format!("{} => would be created", route.pattern),
+        RouteUploadResult::Removed(route) => format!("{} => would be removed", route.pattern),
+        RouteUploadResult::TakenOver((route, previous_script)) => format!(
+            "{} => would be reassigned from {}",
+            route.pattern,
+            previous_script.as_deref().unwrap_or("null worker")
+        ),
+        _ => result.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    use crate::deploy::ZonelessTarget;
+    use crate::settings::toml::{Route, Site, TargetType};
+    use crate::upload::form;
+
+    fn site_target(dir: &Path) -> Target {
+        let site: Site = serde_json::from_value(serde_json::json!({
+            "bucket": dir.join("public"),
+            "entry-point": dir.join("workers-site"),
+        }))
+        .unwrap();
+
+        Target {
+            account_id: "accountid".to_string(),
+            kv_namespaces: vec![KvNamespace {
+                binding: "__STATIC_CONTENT".to_string(),
+                id: "namespaceid".to_string(),
+            }],
+            name: "my-site".to_string(),
+            target_type: TargetType::Webpack,
+            webpack_config: None,
+            site: Some(site),
+            vars: None,
+            text_blobs: None,
+            build: None,
+        }
+    }
+
+    fn route(pattern: &str, script: Option<&str>) -> Route {
+        Route {
+            id: None,
+            script: script.map(str::to_string),
+            pattern: pattern.to_string(),
+        }
+    }
+
+    #[test]
+    fn it_writes_the_parts_of_a_site_to_the_outdir() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = site_target(dir.path());
+        let bucket = dir.path().join("public");
+        fs::create_dir_all(&bucket).unwrap();
+        fs::write(bucket.join("index.html"), "<h1>hello</h1>").unwrap();
+        let bundle_dir = dir.path().join("workers-site").join("worker");
+        fs::create_dir_all(&bundle_dir).unwrap();
+        fs::write(
+            bundle_dir.join("script.js"),
+            "addEventListener('fetch', () => {})",
+        )
+        .unwrap();
+
+        let (_, asset_manifest) = sites::directory_files(&target, &bucket).unwrap();
+        let manifest_json = serde_json::to_string(&asset_manifest.original_keys()).unwrap();
+        let outdir = dir.path().join("parts");
+        let assets = upload_assets(&target, Some(asset_manifest), Some(&outdir)).unwrap();
+        let read = form::read_parts(&outdir).unwrap();
+
+        assert_eq!(
+            fs::read_to_string(read.script_path()).unwrap(),
+            "addEventListener('fetch', () => {})"
+        );
+        assert_eq!(
+            serde_json::to_value(read.bindings()).unwrap(),
+            serde_json::to_value(assets.bindings()).unwrap()
+        );
+        assert_eq!(read.kv_namespaces, target.kv_namespaces);
+        let text_blobs: Vec<(&str, &str)> = read
+            .text_blobs
+            .iter()
+            .map(|blob| (blob.binding.as_str(), blob.data.as_str()))
+            .collect();
+        assert_eq!(
+            text_blobs,
+            vec![("__STATIC_CONTENT_MANIFEST", manifest_json.as_str())]
+        );
+    }
+
+    #[test]
+    fn it_reports_the_routes_schedules_and_assets_publishing_would_change() {
+        let dir = tempfile::tempdir().unwrap();
+        let target = site_target(dir.path());
+        let script_path = dir.path().join("script.js");
+        fs::write(&script_path, "addEventListener('fetch', () => {})").unwrap();
+        let assets = ProjectAssets::new(
+            script_path,
+            Vec::new(),
+            target.kv_namespaces.clone(),
+            Vec::new(),
+            Vec::new(),
+        )
+        .unwrap();
+        let deployments = vec![DeployTarget::WorkersDevDisabled(ZonelessTarget {
+            account_id: "accountid".to_string(),
+            script_name: "my-site".to_string(),
+        })];
+        let planned = Planned {
+            routes: vec![
+                RouteUploadResult::New(route("example.com/*", Some("my-site"))),
+                RouteUploadResult::Removed(route("old.example.com/*", Some("my-site"))),
+                RouteUploadResult::TakenOver((
+                    route("api.example.com/*", Some("my-site")),
+                    Some("other-worker".to_string()),
+                )),
+            ],
+            urls: Vec::new(),
+            removed_schedules: vec!["0 * * * *".to_string()],
+            assets: Some(AssetChanges {
+                to_upload: 2,
+                to_delete: 5,
+            }),
+        };
+
+        let output = report(&target, &assets, &deployments, planned);
+
+        assert!(output.workers_dev_disabled);
+        assert!(output.schedules.is_empty());
+        assert_eq!(
+            render(&output),
+            vec![
+                "Dry run of publishing \"my-site\"",
+                "bindings:",
+                " __STATIC_CONTENT => kv namespace namespaceid",
+                "routes:",
+                " example.com/* => would be created",
+                " old.example.com/* => would be removed",
+                " api.example.com/* => would be reassigned from other-worker",
+                "workers.dev:\n would be disabled",
+                "schedules to remove:",
+                " 0 * * * *",
+                "site assets:\n 2 to upload\n 5 to delete",
+            ]
+            .join("\n")
+        );
+    }
+}
@@ -0,0 +1,371 @@
+use std::env;
+use std::path::{Path, PathBuf};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+
+use crate::build::build_target;
+use crate::commands::kv;
+use crate::deploy::{self, history, DeployTarget, DeploymentSet};
+use crate::http::{self, Feature};
+use crate::kv::bulk;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::sites::{self, AssetManifest, UploadCheckpoint};
+use crate::terminal::emoji;
+use crate::terminal::message::{Message, Output, StdErr, StdOut};
+use crate::upload::{self, form::ProjectAssets};
+
+mod dry_run;
+
+#[derive(Serialize, Deserialize, Default)]
+pub struct PublishOutput {
+    pub success: bool,
+    pub name: String,
+    pub urls: Vec<String>,
+    pub schedules: Vec<String>,
+    // crons that were deployed before but are no longer configured
+    pub removed_schedules: Vec<String>,
+    pub takeovers: Vec<deploy::RouteTakeover>,
+    // whether the script was taken off its workers.dev subdomain
+    pub workers_dev_disabled: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct PublishOpts {
+    // build and assemble the upload without sending anything
+    pub dry_run: bool,
+    // where a dry run should write the upload form parts
+    pub outdir: Option<PathBuf>,
+    // delete routes that point at this script but are no longer configured
+    pub prune_routes: bool,
+    // take over routes that point at another script
+    pub force_routes: bool,
+    // continue the upload of an interrupted publish from its checkpoint
+    pub resume: bool,
+}
+
+impl PublishOpts {
+    fn deploy_opts(&self) -> deploy::DeployOpts {
+        deploy::DeployOpts {
+            prune_routes: self.prune_routes,
+            force_routes: self.force_routes,
+        }
+    }
+}
+
+pub fn publish(
+    user: &GlobalUser,
+    target: &mut Target,
+    mut deployments: DeploymentSet,
+    out: Output,
+    opts: PublishOpts,
+) -> Result<(), failure::Error> {
+    validate_target_required_fields_present(target)?;
+    if opts.prune_routes {
+        deploy::add_pruned_zones(&target.name, &mut deployments)?;
+    }
+
+    if opts.dry_run {
+        return dry_run::dry_run(user, target, &deployments, &opts, out);
+    }
+
+    let deploy_opts = opts.deploy_opts();
+    let deploy =
+        |target: &Target| match deploy::worker(&user, &target.name, &deployments, &deploy_opts) {
+            Ok(deploy::DeployResults {
+                urls,
+                schedules,
+                takeovers,
+                workers_dev_disabled,
+            }) => {
+                let result_msg = match (urls.as_slice(), schedules.as_slice()) {
+                    ([], []) => "Successfully published your script".to_owned(),
+                    ([], schedules) => format!(
+                        "Successfully published your script with this schedule\n {}",
+                        schedules.join("\n ")
+                    ),
+                    (urls, []) => format!(
+                        "Successfully published your script to\n {}",
+                        urls.join("\n ")
+                    ),
+                    (urls, schedules) => format!(
+                        "Successfully published your script to\n {}\nwith this schedule\n {}",
+                        urls.join("\n "),
+                        schedules.join("\n ")
+                    ),
+                };
+                StdErr::success(&result_msg);
+                if workers_dev_disabled {
+                    StdErr::info(&format!(
+                        "{} is no longer available on your workers.dev subdomain",
+                        target.name
+                    ));
+                }
+
+                let removed_schedules = deploy::clear_stale_schedules(
+                    user,
+                    &target.account_id,
+                    &target.name,
+                    &deployments,
+                );
+                if !removed_schedules.is_empty() {
+                    StdErr::info(&format!(
+                        "Removed schedules that are no longer in your configuration file\n {}",
+                        removed_schedules.join("\n ")
+                    ));
+                }
+
+                if out == Output::Json {
+                    StdOut::as_json(&PublishOutput {
+                        success: true,
+                        name: target.name.clone(),
+                        urls,
+                        schedules,
+                        removed_schedules,
+                        takeovers,
+                        workers_dev_disabled,
+                    });
+                }
+                Ok(())
+            }
+            Err(e) => Err(e),
+        };
+
+    // Build the script before uploading and log build result
+    let build_result = build_target(&target);
+    match build_result {
+        Ok(msg) => {
+            StdErr::success(&msg);
+            Ok(())
+        }
+        Err(e) => Err(e),
+    }?;
+    if let Some(site_config) = &target.site {
+        let path = &site_config.bucket.clone();
+        let retain_versions = site_config.retain_versions;
+        validate_bucket_location(path)?;
+        if let Some(retain_versions) = retain_versions {
+            sites::validate_retain_versions(retain_versions)?;
+        }
+
+        let site_namespace = sites::add_namespace(user, target, false)?;
+
+        let mut checkpoint = match UploadCheckpoint::load(&site_namespace.id)? {
+            Some(checkpoint) if opts.resume => {
+                StdErr::info("Resuming the upload of the interrupted publish");
+                checkpoint
+            }
+            interrupted => {
+                if opts.resume {
+                    StdErr::info(
+                        "There is no interrupted publish to resume, publishing from the start",
+                    );
+                } else if interrupted.is_some() {
+                    StdErr::info("The last publish was interrupted, run `wrangler publish --resume` next time to skip the files it uploaded");
+                }
+                kv::validate_target(target)?;
+                let remote_keys = sites::remote_keys(target, user, &site_namespace.id)?;
+                UploadCheckpoint::new(&site_namespace.id, remote_keys)?
+            }
+        };
+        // like the deployment history, the checkpoint is only kept on a best effort basis
+        let mut checkpoint_failed = false;
+        if let Err(e) = checkpoint.save() {
+            warn_checkpoint_failed(e);
+            checkpoint_failed = true;
+        }
+
+        let (to_upload, to_delete, asset_manifest) = sites::diff(target, &path, checkpoint.keys())?;
+
+        // First, upload all existing files in bucket directory
+        StdErr::working("Uploading site files");
+        let upload_progress_bar = if to_upload.len() > bulk::BATCH_KEY_MAX {
+            let upload_progress_bar = ProgressBar::new(to_upload.len() as u64);
+            upload_progress_bar
+                .set_style(ProgressStyle::default_bar().template("{wide_bar} {pos}/{len}\n{msg}"));
+            Some(upload_progress_bar)
+        } else {
+            None
+        };
+
+        // every uploaded batch is added to the checkpoint, so an interrupted publish can
+        // be resumed without uploading it again
+        bulk::put_files(
+            target,
+            user,
+            &site_namespace.id,
+            to_upload,
+            &upload_progress_bar,
+            &mut |uploaded| {
+                if checkpoint_failed {
+                    return;
+                }
+                if let Err(e) = checkpoint.add(uploaded.iter().map(|file| file.key.clone())) {
+                    warn_checkpoint_failed(e);
+                    checkpoint_failed = true;
+                }
+            },
+        )?;
+
+        if let Some(pb) = upload_progress_bar {
+            pb.finish_with_message("Done Uploading");
+        }
+
+        let upload_client = http::featured_legacy_auth_client(user, Feature::Sites);
+
+        // Next, upload and deploy the worker with the updated asset_manifest
+        let assets = upload::form::project_assets(target, Some(asset_manifest.clone()))?;
+        upload::script(&upload_client, &target, &assets)?;
+
+        deploy(target)?;
+        let generation_recorded =
+            sites::record_generation(target, user, &site_namespace.id, &asset_manifest);
+        record_deployment(target, &assets, Some(asset_manifest), &deployments);
+
+        // Finally, remove any stale files
+        if let Some(retain_versions) = retain_versions {
+            // without the generation of this publish its files could be collected, so nothing is;
+            // like recording it, collecting doesn't fail a publish that went through
+            if !generation_recorded {
+                StdErr::info("No stale files were deleted");
+            } else if let Err(e) =
+                sites::collect_garbage(target, user, &site_namespace.id, retain_versions)
+            {
+                StdErr::warn(&format!(
+                    "Could not delete the files that the last {} generation(s) don't use, run `wrangler site gc` to try again: {}",
+                    retain_versions, e
+                ));
+            }
+        } else if !to_delete.is_empty() {
+            StdErr::info("Deleting stale files...");
+
+            let delete_progress_bar = if to_delete.len() > bulk::BATCH_KEY_MAX {
+                let delete_progress_bar = ProgressBar::new(to_delete.len() as u64);
+                delete_progress_bar.set_style(
+                    ProgressStyle::default_bar().template("{wide_bar} {pos}/{len}\n{msg}"),
+                );
+                Some(delete_progress_bar)
+            } else {
+                None
+            };
+
+            bulk::delete(
+                target,
+                user,
+                &site_namespace.id,
+                to_delete,
+                &delete_progress_bar,
+            )?;
+
+            if let Some(pb) = delete_progress_bar {
+                pb.finish_with_message("Done deleting");
+            }
+        }
+
+        if let Err(e) = UploadCheckpoint::remove(&site_namespace.id) {
+            StdErr::warn(&format!("Could not remove the upload checkpoint: {}", e));
+        }
+    } else {
+        let upload_client = http::legacy_auth_client(user);
+
+        let assets = upload::form::project_assets(target, None)?;
+        upload::script(&upload_client, &target, &assets)?;
+        deploy(target)?;
+        record_deployment(target, &assets, None, &deployments);
+    }
+
+    Ok(())
+}
+
+// A publish that went through shouldn't be reported as failed just because it couldn't be
+// added to the history, so this only warns.
+fn record_deployment(
+    target: &Target,
+    assets: &ProjectAssets,
+    asset_manifest: Option<AssetManifest>,
+    deployments: &[DeployTarget],
+) {
+    match history::record(&target.name, assets, asset_manifest, deployments, None) {
+        Ok(deployment) => StdErr::info(&format!("Recorded as deployment {}", deployment.id)),
+        Err(e) => StdErr::warn(&format!(
+            "Could not record this deployment in the project history: {}",
+            e
+        )),
+    }
+}
+
+fn warn_checkpoint_failed(e: failure::Error) {
+    StdErr::warn(&format!(
+        "Could not save the upload checkpoint, this publish can't be resumed if it is interrupted: {}",
+        e
+    ));
+}
+
+// We don't want folks setting their bucket to the top level directory,
+// which is where wrangler commands are always called from.
+pub fn validate_bucket_location(bucket: &PathBuf) -> Result<(), failure::Error> {
+    // TODO: this should really use a convenience function for "Wrangler Project Root"
+    let current_dir = env::current_dir()?;
+    if bucket.as_os_str() == current_dir {
+        failure::bail!(
+            "{} Your bucket cannot be set to the parent directory of your configuration file",
+            emoji::WARN
+        )
+    }
+    let path = Path::new(&bucket);
+    if !path.exists() {
+        failure::bail!(
+            "{} bucket directory \"{}\" does not exist",
+            emoji::WARN,
+            path.display()
+        )
+    } else if !path.is_dir() {
+        failure::bail!(
+            "{} bucket \"{}\" is not a directory",
+            emoji::WARN,
+            path.display()
+        )
+    }
+
+    Ok(())
+}
+
+fn validate_target_required_fields_present(target: &Target) -> Result<(), failure::Error> {
+    let mut missing_fields = Vec::new();
+
+    if target.account_id.is_empty() {
+        missing_fields.push("account_id")
+    };
+    if target.name.is_empty() {
+        missing_fields.push("name")
+    };
+
+    for kv in &target.kv_namespaces {
+        if kv.binding.is_empty() {
+            missing_fields.push("kv-namespace binding")
+        }
+
+        if kv.id.is_empty() {
+            missing_fields.push("kv-namespace id")
+        }
+    }
+
+    let (field_pluralization, is_are) = match missing_fields.len() {
+        n if n >= 2 => ("fields", "are"),
+        1 => ("field", "is"),
+        _ => ("", ""),
+    };
+
+    if !missing_fields.is_empty() {
+        failure::bail!(
+            "{} Your configuration file is missing the {} {:?} which {} required to publish your worker!",
+            emoji::WARN,
+            field_pluralization,
+            missing_fields,
+            is_are,
+        );
+    };
+
+    Ok(())
+}
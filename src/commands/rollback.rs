@@ -0,0 +1,145 @@
+use std::collections::HashSet;
+
+use crate::commands::kv;
+use crate::deploy::{self, history, DeployOpts};
+use crate::http::{self, Feature};
+use crate::kv::key::KeyList;
+use crate::settings::binding::Binding;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::sites;
+use crate::terminal::message::{Message, StdErr};
+use crate::upload;
+
+// Re-uploads a recorded deployment exactly as it was published, and points its routes,
+// workers.dev subdomain and schedules back at it.
+pub fn rollback(
+    user: &GlobalUser,
+    target: &Target,
+    to: Option<&str>,
+) -> Result<(), failure::Error> {
+    let deployment = history::get(&target.name, to)?;
+    StdErr::working(&format!(
+        "Rolling back {} to deployment {} from {}",
+        target.name, deployment.id, deployment.created_on
+    ));
+
+    let assets = deployment.project_assets()?;
+
+    let upload_client = if deployment.asset_manifest.is_some() {
+        warn_on_missing_site_assets(user, target, &deployment)?;
+        http::featured_legacy_auth_client(user, Feature::Sites)
+    } else {
+        http::legacy_auth_client(user)
+    };
+
+    upload::script(&upload_client, target, &assets)?;
+
+    let results = deploy::worker(
+        user,
+        &target.name,
+        &deployment.deployments,
+        &DeployOpts::default(),
+    )?;
+    // the files of the restored deployment are live again, so they are kept like a publish's
+    if let (Some(asset_manifest), Some(namespace_id)) =
+        (&deployment.asset_manifest, site_namespace_id(&deployment))
+    {
+        sites::record_generation(target, user, namespace_id, asset_manifest);
+    }
+    // the deployment being restored may not have had any crons
+    let removed_schedules = deploy::clear_stale_schedules(
+        user,
+        &target.account_id,
+        &target.name,
+        &deployment.deployments,
+    );
+
+    let rolled_back = history::record(
+        &target.name,
+        &assets,
+        deployment.asset_manifest.clone(),
+        &deployment.deployments,
+        Some(deployment.id.clone()),
+    )?;
+
+    let mut msg = format!(
+        "Rolled back {} to deployment {}, recorded as deployment {}",
+        target.name, deployment.id, rolled_back.id
+    );
+    if !results.urls.is_empty() {
+        msg.push_str(&format!("\n {}", results.urls.join("\n ")));
+    }
+    if !results.schedules.is_empty() {
+        msg.push_str(&format!(
+            "\nwith this schedule\n {}",
+            results.schedules.join("\n ")
+        ));
+    }
+    if results.workers_dev_disabled {
+        msg.push_str("\nand disabled its workers.dev subdomain");
+    }
+    if !removed_schedules.is_empty() {
+        msg.push_str(&format!(
+            "\nand removed the schedule\n {}",
+            removed_schedules.join("\n ")
+        ));
+    }
+    StdErr::success(&msg);
+
+    Ok(())
+}
+
+// Later publishes delete site files that they no longer use, unless they are kept with
+// `[site] retain_versions`, so an older asset manifest may point at keys that are gone.
+fn warn_on_missing_site_assets(
+    user: &GlobalUser,
+    target: &Target,
+    deployment: &history::Deployment,
+) -> Result<(), failure::Error> {
+    let asset_manifest = match &deployment.asset_manifest {
+        Some(asset_manifest) => asset_manifest,
+        None => return Ok(()),
+    };
+    let namespace_id = match site_namespace_id(deployment) {
+        Some(namespace_id) => namespace_id,
+        None => return Ok(()),
+    };
+
+    let client = http::cf_v4_client(user)?;
+    let mut remote_keys: HashSet<String> = HashSet::new();
+    for remote_key in KeyList::new(target, client, namespace_id, None)? {
+        match remote_key {
+            Ok(remote_key) => {
+                remote_keys.insert(remote_key.name);
+            }
+            Err(e) => failure::bail!(kv::format_error(e)),
+        }
+    }
+
+    let missing = asset_manifest
+        .kv_keys()
+        .filter(|key| !remote_keys.contains(*key))
+        .count();
+    if missing > 0 {
+        StdErr::warn(&format!(
+            "{} site file(s) used by deployment {} have since been deleted and will be missing until you publish again. Set `retain_versions` in [site] to keep the files of earlier publishes.",
+            missing, deployment.id
+        ));
+    }
+
+    Ok(())
+}
+
+// The Workers Sites namespace the deployment was bound to, if it had one.
+fn site_namespace_id(deployment: &history::Deployment) -> Option<&String> {
+    deployment
+        .bindings
+        .iter()
+        .find_map(|binding| match binding {
+            Binding::KvNamespace { name, namespace_id } if name == "__STATIC_CONTENT" => {
+                Some(namespace_id)
+            }
+            _ => None,
+        })
+}
@@ -1,55 +1,246 @@
-extern crate serde_json;
-
-use cloudflare::endpoints::workers::{DeleteRoute, ListRoutes};
+use cloudflare::endpoints::workers::{CreateRoute, CreateRouteParams, DeleteRoute, ListRoutes};
 use cloudflare::framework::apiclient::ApiClient;
+use prettytable::{Cell, Row, Table};
 
+use crate::deploy::{DeployTarget, ZoneResolver};
 use crate::http;
 use crate::settings::global_user::GlobalUser;
-use crate::terminal::message::{Message, StdOut};
-pub fn list(zone_identifier: String, user: &GlobalUser) -> Result<(), failure::Error> {
-    let client = http::cf_v4_client(user)?;
-
-    let result = client.request(&ListRoutes {
-        zone_identifier: &zone_identifier,
-    });
+use crate::settings::toml::{Manifest, Route};
+use crate::terminal::message::{Message, Output, StdOut};
 
-    match result {
-        Ok(success) => {
-            let routes = success.result;
-            println!("{}", serde_json::to_string(&routes)?);
+// The zones `wrangler route` commands act on: the configured `zone_id`, along with every zone
+// the configured routes are on.
+pub fn zone_ids(manifest: &Manifest, env: Option<&str>) -> Result<Vec<String>, failure::Error> {
+    let mut zone_ids: Vec<String> = configured_zone_id(manifest, env)?.into_iter().collect();
+    if !manifest.route_patterns(env)?.is_empty() {
+        for deployment in
+            manifest.get_deployments_with_zones(env, &mut zone_resolver(manifest, env)?)?
+        {
+            if let DeployTarget::Zoned(zoned) = deployment {
+                if !zone_ids.contains(&zoned.zone_id) {
+                    zone_ids.push(zoned.zone_id);
+                }
+            }
         }
+    }
+    if zone_ids.is_empty() {
+        failure::bail!(
+            "You must specify a zone_id or routes in your configuration file to use `wrangler route` commands."
+        )
+    }
 
-        Err(e) => failure::bail!("{}", http::format_error(e, None)),
+    Ok(zone_ids)
+}
+
+// Lists the routes on the zones of the project, marking the ones declared in the
+// configuration file and the script each of them points at.
+pub fn list(
+    manifest: &Manifest,
+    env: Option<&str>,
+    user: &GlobalUser,
+    out: Output,
+) -> Result<(), failure::Error> {
+    let mut routes = Vec::new();
+    for zone_id in zone_ids(manifest, env)? {
+        routes.extend(fetch_routes(user, &zone_id)?);
     }
+
+    if out == Output::Json {
+        StdOut::as_json(&routes);
+        return Ok(());
+    }
+
+    let script_name = manifest.worker_name(env);
+    let declared = manifest.route_patterns(env)?;
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Pattern"),
+        Cell::new("Script"),
+        Cell::new("In Config"),
+        Cell::new("Belongs To"),
+    ]));
+    for route in &routes {
+        let belongs_to = match route.script.as_deref() {
+            Some(script) if script == script_name => "this script",
+            Some(_) => "another script",
+            None => "no script",
+        };
+        table.add_row(Row::new(vec![
+            Cell::new(&route.pattern),
+            Cell::new(route.script.as_deref().unwrap_or("")),
+            Cell::new(yes_no(declared.contains(&route.pattern))),
+            Cell::new(belongs_to),
+        ]));
+    }
+    // declared routes that haven't been published yet
+    for pattern in &declared {
+        if !routes.iter().any(|route| &route.pattern == pattern) {
+            table.add_row(Row::new(vec![
+                Cell::new(pattern),
+                Cell::new(""),
+                Cell::new(yes_no(true)),
+                Cell::new("not published"),
+            ]));
+        }
+    }
+    println!("{}", &table);
+
     Ok(())
 }
 
-pub fn delete(
-    zone_identifier: String,
+// Creates a route for `pattern` on the zone it belongs to, pointing at `script`, or at
+// the worker of the environment when no script is given.
+pub fn create(
+    manifest: &Manifest,
+    env: Option<&str>,
     user: &GlobalUser,
-    route_id: &str,
+    pattern: &str,
+    script: Option<&str>,
 ) -> Result<(), failure::Error> {
-    let client = http::cf_v4_client(user)?;
+    let zone_id = zone_for_pattern(manifest, env, pattern)?;
+    let script = match script {
+        Some(script) => script.to_string(),
+        None => manifest.worker_name(env),
+    };
 
-    let result = client.request(&DeleteRoute {
-        zone_identifier: &zone_identifier,
-        identifier: route_id,
+    let client = http::cf_v4_client(user)?;
+    let result = client.request(&CreateRoute {
+        zone_identifier: &zone_id,
+        params: CreateRouteParams {
+            pattern: pattern.to_string(),
+            script: Some(script.clone()),
+        },
     });
 
     match result {
-        Ok(success) => {
-            let msg = format!("Successfully deleted route with id {}", success.result.id);
-            StdOut::success(&msg);
-        }
-
+        Ok(success) => StdOut::success(&format!(
+            "Created route {} => {} with id {}",
+            pattern, script, success.result.id
+        )),
         Err(e) => failure::bail!("{}", http::format_error(e, Some(&error_suggestions))),
     }
+
     Ok(())
 }
 
+// Deletes the route with the given pattern (or id) from the zones of the project.
+pub fn delete(
+    manifest: &Manifest,
+    env: Option<&str>,
+    user: &GlobalUser,
+    pattern: &str,
+) -> Result<(), failure::Error> {
+    // a route id has no hostname to find a zone by, so it is looked for in the zones of the
+    // project
+    let zone_ids = if is_route_id(pattern) {
+        zone_ids(manifest, env)?
+    } else {
+        vec![zone_for_pattern(manifest, env, pattern)?]
+    };
+
+    for zone_id in &zone_ids {
+        let route = fetch_routes(user, zone_id)?
+            .into_iter()
+            .find(|route| route.pattern == pattern || route.id.as_deref() == Some(pattern));
+        if let Some(route) = route {
+            let client = http::cf_v4_client(user)?;
+            let result = client.request(&DeleteRoute {
+                zone_identifier: zone_id,
+                identifier: route.id.as_deref().unwrap_or_default(),
+            });
+
+            match result {
+                Ok(_) => {
+                    StdOut::success(&format!("Successfully deleted route {}", route.pattern));
+                    return Ok(());
+                }
+                Err(e) => failure::bail!("{}", http::format_error(e, Some(&error_suggestions))),
+            }
+        }
+    }
+
+    failure::bail!(
+        "There is no route {} on the zones of your project. Run `wrangler route list` to see the routes.",
+        pattern
+    )
+}
+
+fn configured_zone_id(
+    manifest: &Manifest,
+    env: Option<&str>,
+) -> Result<Option<String>, failure::Error> {
+    let env_zone_id = match manifest.get_environment(env)? {
+        Some(environment) => environment.zone_id.as_ref(),
+        None => None,
+    };
+
+    Ok(env_zone_id
+        .or_else(|| manifest.zone_id.as_ref())
+        .filter(|zone_id| !zone_id.is_empty())
+        .cloned())
+}
+
+// A declared route is on the zone it is deployed to; any other pattern is on the zone its
+// hostname belongs to, or else the configured zone.
+fn zone_for_pattern(
+    manifest: &Manifest,
+    env: Option<&str>,
+    pattern: &str,
+) -> Result<String, failure::Error> {
+    if manifest.route_patterns(env)?.iter().any(|p| p == pattern) {
+        for deployment in
+            manifest.get_deployments_with_zones(env, &mut zone_resolver(manifest, env)?)?
+        {
+            if let DeployTarget::Zoned(zoned) = deployment {
+                if zoned.routes.iter().any(|route| route.pattern == pattern) {
+                    return Ok(zoned.zone_id);
+                }
+            }
+        }
+    }
+
+    match zone_resolver(manifest, env)?.resolve(pattern) {
+        Ok(zone) => Ok(zone.id),
+        Err(e) => match configured_zone_id(manifest, env)? {
+            Some(zone_id) => {
+                log::info!("falling back to the configured zone for {}: {}", pattern, e);
+                Ok(zone_id)
+            }
+            None => Err(e),
+        },
+    }
+}
+
+fn zone_resolver(manifest: &Manifest, env: Option<&str>) -> Result<ZoneResolver, failure::Error> {
+    Ok(ZoneResolver::new(&manifest.get_account_id(env)?))
+}
+
+fn fetch_routes(user: &GlobalUser, zone_identifier: &str) -> Result<Vec<Route>, failure::Error> {
+    let client = http::cf_v4_client(user)?;
+
+    match client.request(&ListRoutes { zone_identifier }) {
+        Ok(success) => Ok(success.result.iter().map(Route::from).collect()),
+        Err(e) => failure::bail!("{}", http::format_error(e, None)),
+    }
+}
+
+fn is_route_id(pattern: &str) -> bool {
+    pattern.len() == 32 && pattern.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+fn yes_no(value: bool) -> &'static str {
+    if value {
+        "yes"
+    } else {
+        "no"
+    }
+}
+
 fn error_suggestions(code: u16) -> &'static str {
     match code {
-        10005 => "Confirm the route id by running `wrangler route list`",
+        10005 => "Confirm the route by running `wrangler route list`",
+        10020 => "That pattern already has a route. Run `wrangler route list` to see which script it points at",
         _ => "",
     }
 }
@@ -0,0 +1,48 @@
+use crate::commands::kv;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::sites::{self, UploadCheckpoint};
+use crate::terminal::message::{Message, StdOut};
+
+// Deletes the files of a Workers Site that none of its last `retain_versions` generations use.
+pub fn gc(user: &GlobalUser, target: &Target) -> Result<(), failure::Error> {
+    let site = match &target.site {
+        Some(site) => site,
+        None => failure::bail!("`wrangler site gc` only works for Workers Sites, and your configuration file has no [site] section"),
+    };
+    let retain_versions = match site.retain_versions {
+        Some(retain_versions) => retain_versions,
+        None => failure::bail!(
+            "Set `retain_versions` in [site] to the number of publishes whose files should be kept"
+        ),
+    };
+    kv::validate_target(target)?;
+
+    let site_namespace = match sites::find_namespace(user, target, false)? {
+        Some(site_namespace) => site_namespace,
+        None => failure::bail!(
+            "{} has no Workers Sites namespace yet, it is created the first time you publish",
+            target.name
+        ),
+    };
+    // the files an interrupted publish uploaded aren't in any generation yet
+    if UploadCheckpoint::load(&site_namespace.id)?.is_some() {
+        failure::bail!("The last publish of {} was interrupted, finish it with `wrangler publish --resume` first", target.name)
+    }
+
+    let garbage = sites::collect_garbage(target, user, &site_namespace.id, retain_versions)?;
+    if garbage.keys.is_empty() && garbage.generations.is_empty() {
+        StdOut::info(&format!(
+            "Every file is used by the last {} generation(s), there is nothing to delete",
+            retain_versions
+        ));
+    } else {
+        StdOut::success(&format!(
+            "Deleted {} file(s) and {} expired generation(s)",
+            garbage.keys.len(),
+            garbage.generations.len()
+        ));
+    }
+
+    Ok(())
+}
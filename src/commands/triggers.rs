@@ -0,0 +1,103 @@
+use chrono::Utc;
+use prettytable::{Cell, Row, Table};
+
+use crate::deploy::ScheduleTarget;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::terminal::interactive;
+use crate::terminal::message::{Message, StdOut};
+
+// Prints the schedules currently deployed for the script, which can differ from the
+// configured ones until the next publish.
+pub fn list(user: &GlobalUser, target: &Target) -> Result<(), failure::Error> {
+    let schedules = schedule_target(target)?.fetch(user)?;
+    if schedules.is_empty() {
+        StdOut::info(&format!("{} has no schedules deployed", target.name));
+        return Ok(());
+    }
+
+    let mut table = Table::new();
+    table.add_row(Row::new(vec![
+        Cell::new("Cron"),
+        Cell::new("Created"),
+        Cell::new("Modified"),
+    ]));
+    for schedule in schedules {
+        table.add_row(Row::new(vec![
+            Cell::new(&schedule.cron),
+            Cell::new(schedule.created_on.as_deref().unwrap_or("")),
+            Cell::new(schedule.modified_on.as_deref().unwrap_or("")),
+        ]));
+    }
+    println!("{}", &table);
+
+    Ok(())
+}
+
+// Removes every schedule deployed for the script, whatever the configuration says.
+pub fn clear(
+    user: &GlobalUser,
+    target: &Target,
+    skip_confirmation: bool,
+) -> Result<(), failure::Error> {
+    let schedules = schedule_target(target)?;
+    let deployed = schedules.fetch(user)?;
+    if deployed.is_empty() {
+        StdOut::info(&format!("{} has no schedules deployed", target.name));
+        return Ok(());
+    }
+
+    if !skip_confirmation {
+        let confirmed = interactive::confirm(&format!(
+            "Are you sure you want to remove the {} schedule(s) of {}?",
+            deployed.len(),
+            target.name
+        ))?;
+        if !confirmed {
+            StdOut::info(&format!("Not removing the schedules of {}", target.name));
+            return Ok(());
+        }
+    }
+
+    schedules.clear(user)?;
+    StdOut::success(&format!(
+        "Removed the schedules of {}\n {}",
+        target.name,
+        deployed
+            .iter()
+            .map(|schedule| schedule.cron.as_str())
+            .collect::<Vec<_>>()
+            .join("\n ")
+    ));
+
+    Ok(())
+}
+
+// Prints the next `count` times, in UTC, that each configured cron fires.
+pub fn preview(schedule: Option<&ScheduleTarget>, count: usize) -> Result<(), failure::Error> {
+    let schedule = match schedule {
+        Some(schedule) => schedule,
+        None => {
+            StdOut::info("There are no crons in the [triggers] section of your configuration file");
+            return Ok(());
+        }
+    };
+
+    let now = Utc::now();
+    for cron in schedule.parsed_crons()? {
+        println!("{}", cron);
+        let times = cron.upcoming(now, count);
+        if times.is_empty() {
+            println!("  never fires");
+        }
+        for time in times {
+            println!("  {}", time.format("%a %Y-%m-%d %H:%M UTC"));
+        }
+    }
+
+    Ok(())
+}
+
+fn schedule_target(target: &Target) -> Result<ScheduleTarget, failure::Error> {
+    ScheduleTarget::build(target.account_id.clone(), target.name.clone(), Vec::new())
+}
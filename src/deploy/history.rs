@@ -0,0 +1,268 @@
+use std::fs;
+use std::hash::Hasher;
+use std::path::{Path, PathBuf};
+
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use twox_hash::XxHash64;
+
+use crate::deploy::{DeployTarget, DeploymentSet};
+use crate::settings::binding::Binding;
+use crate::settings::get_project_state_dir;
+use crate::sites::AssetManifest;
+use crate::terminal::message::{Message, StdErr};
+use crate::upload::form::{self, ProjectAssets};
+
+const DEPLOYMENT_FILE_NAME: &str = "deployment.json";
+const ARTIFACT_DIR_NAME: &str = "artifact";
+// older deployments are dropped from the history once it grows past this
+const MAX_DEPLOYMENTS: usize = 20;
+
+/// A successful publish, with everything needed to publish it again.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Deployment {
+    pub id: String,
+    pub created_on: String,
+    pub script_name: String,
+    pub script_hash: String,
+    pub bindings: Vec<Binding>,
+    pub asset_manifest: Option<AssetManifest>,
+    pub deployments: DeploymentSet,
+    // the id of the deployment this one restored, if it was made by `wrangler rollback`
+    pub rollback_of: Option<String>,
+}
+
+impl Deployment {
+    pub fn routes(&self) -> Vec<String> {
+        self.deployments
+            .iter()
+            .filter_map(|deployment| match deployment {
+                DeployTarget::Zoned(zoned) => Some(zoned.routes.iter().map(|r| r.pattern.clone())),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn crons(&self) -> Vec<String> {
+        self.deployments
+            .iter()
+            .filter_map(|deployment| match deployment {
+                DeployTarget::Schedule(schedule) => Some(schedule.crons.clone()),
+                _ => None,
+            })
+            .flatten()
+            .collect()
+    }
+
+    pub fn workers_dev(&self) -> bool {
+        self.deployments
+            .iter()
+            .any(|deployment| matches!(deployment, DeployTarget::Zoneless(_)))
+    }
+
+    // The script, modules and bindings exactly as they were uploaded.
+    pub fn project_assets(&self) -> Result<ProjectAssets, failure::Error> {
+        let artifact_dir = history_dir(&self.script_name)?
+            .join(&self.id)
+            .join(ARTIFACT_DIR_NAME);
+        form::read_parts(&artifact_dir)
+    }
+}
+
+// Records a successful publish of `script_name` in the project's deployment history.
+pub fn record(
+    script_name: &str,
+    assets: &ProjectAssets,
+    asset_manifest: Option<AssetManifest>,
+    deployments: &[DeployTarget],
+    rollback_of: Option<String>,
+) -> Result<Deployment, failure::Error> {
+    let now = Utc::now();
+    let deployment = Deployment {
+        id: now.format("%Y%m%d%H%M%S%3f").to_string(),
+        created_on: now.to_rfc3339(),
+        script_name: script_name.to_string(),
+        script_hash: script_hash(&assets.script_path())?,
+        bindings: assets.bindings(),
+        asset_manifest,
+        deployments: deployments.to_vec(),
+        rollback_of,
+    };
+
+    let history_dir = history_dir(script_name)?;
+    let deployment_dir = history_dir.join(&deployment.id);
+    form::write_parts(assets, &deployment_dir.join(ARTIFACT_DIR_NAME))?;
+    // written next to the record and renamed over it, so an interrupted write can't leave
+    // half a record behind
+    let deployment_path = deployment_dir.join(DEPLOYMENT_FILE_NAME);
+    let partial_path = deployment_path.with_extension("json.partial");
+    fs::write(&partial_path, serde_json::to_string_pretty(&deployment)?)?;
+    fs::rename(partial_path, deployment_path)?;
+    log::info!("Recorded deployment {}", deployment.id);
+
+    let recorded = read_all(&history_dir)?;
+    if recorded.len() > MAX_DEPLOYMENTS {
+        for expired in &recorded[..recorded.len() - MAX_DEPLOYMENTS] {
+            log::info!("Removing deployment {} from the history", expired.id);
+            fs::remove_dir_all(history_dir.join(&expired.id))?;
+        }
+    }
+
+    Ok(deployment)
+}
+
+// Every recorded deployment of `script_name`, oldest first.
+pub fn list(script_name: &str) -> Result<Vec<Deployment>, failure::Error> {
+    read_all(&history_dir(script_name)?)
+}
+
+// Removes every recorded deployment of `script_name`, e.g. once the script is deleted.
+pub fn forget(script_name: &str) -> Result<(), failure::Error> {
+    let history_dir = history_dir(script_name)?;
+    if history_dir.exists() {
+        fs::remove_dir_all(history_dir)?;
+    }
+
+    Ok(())
+}
+
+// Finds the deployment with the given id, or the one before the most recent when no id is given.
+pub fn get(script_name: &str, id: Option<&str>) -> Result<Deployment, failure::Error> {
+    select(list(script_name)?, script_name, id)
+}
+
+fn select(
+    mut deployments: Vec<Deployment>,
+    script_name: &str,
+    id: Option<&str>,
+) -> Result<Deployment, failure::Error> {
+    match id {
+        Some(id) => match deployments.into_iter().find(|d| d.id == id) {
+            Some(deployment) => Ok(deployment),
+            None => failure::bail!(
+                "There is no deployment with id {} in the history of {}. Run `wrangler deployments list` to see the recorded deployments.",
+                id,
+                script_name
+            ),
+        },
+        None => {
+            if deployments.len() < 2 {
+                failure::bail!(
+                    "There is no earlier deployment of {} to roll back to",
+                    script_name
+                )
+            }
+            let previous = deployments.len() - 2;
+            Ok(deployments.remove(previous))
+        }
+    }
+}
+
+fn history_dir(script_name: &str) -> Result<PathBuf, failure::Error> {
+    Ok(get_project_state_dir()?
+        .join("deployments")
+        .join(script_name))
+}
+
+fn read_all(history_dir: &Path) -> Result<Vec<Deployment>, failure::Error> {
+    let mut deployments = Vec::new();
+    if !history_dir.exists() {
+        return Ok(deployments);
+    }
+
+    for entry in fs::read_dir(history_dir)? {
+        let deployment_file = entry?.path().join(DEPLOYMENT_FILE_NAME);
+        if deployment_file.is_file() {
+            // one unreadable record, e.g. one that was edited by hand, shouldn't take the rest
+            // of the history with it
+            match read_deployment(&deployment_file) {
+                Ok(deployment) => deployments.push(deployment),
+                Err(e) => StdErr::warn(&format!(
+                    "Skipping the deployment recorded in {}, it could not be read: {}",
+                    deployment_file.display(),
+                    e
+                )),
+            }
+        }
+    }
+    // ids are timestamps, so they sort chronologically
+    deployments.sort_by(|a, b| a.id.cmp(&b.id));
+
+    Ok(deployments)
+}
+
+fn read_deployment(deployment_file: &Path) -> Result<Deployment, failure::Error> {
+    let deployment_json = fs::read_to_string(deployment_file)?;
+    Ok(serde_json::from_str(&deployment_json)?)
+}
+
+fn script_hash(script_path: &Path) -> Result<String, failure::Error> {
+    let mut hasher = XxHash64::default();
+    hasher.write(&fs::read(script_path)?);
+    Ok(format!("{:x}", hasher.finish()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deployment(id: &str) -> Deployment {
+        Deployment {
+            id: id.to_string(),
+            created_on: String::new(),
+            script_name: "my-worker".to_string(),
+            script_hash: String::new(),
+            bindings: Vec::new(),
+            asset_manifest: None,
+            deployments: Vec::new(),
+            rollback_of: None,
+        }
+    }
+
+    #[test]
+    fn it_selects_the_previous_deployment_by_default() {
+        let deployments = vec![deployment("1"), deployment("2"), deployment("3")];
+
+        let selected = select(deployments, "my-worker", None).unwrap();
+
+        assert_eq!(selected.id, "2");
+    }
+
+    #[test]
+    fn it_selects_a_deployment_by_id() {
+        let deployments = vec![deployment("1"), deployment("2"), deployment("3")];
+
+        let selected = select(deployments, "my-worker", Some("1")).unwrap();
+
+        assert_eq!(selected.id, "1");
+    }
+
+    #[test]
+    fn it_skips_deployments_that_cant_be_read() {
+        let history_dir = tempfile::tempdir().unwrap();
+        for (id, deployment_json) in &[
+            ("1", serde_json::to_string(&deployment("1")).unwrap()),
+            ("2", "{\"id\": \"2\", \"created_on".to_string()),
+            ("3", serde_json::to_string(&deployment("3")).unwrap()),
+        ] {
+            let deployment_dir = history_dir.path().join(id);
+            fs::create_dir_all(&deployment_dir).unwrap();
+            fs::write(deployment_dir.join(DEPLOYMENT_FILE_NAME), deployment_json).unwrap();
+        }
+
+        let ids: Vec<String> = read_all(history_dir.path())
+            .unwrap()
+            .into_iter()
+            .map(|deployment| deployment.id)
+            .collect();
+
+        assert_eq!(ids, vec!["1", "3"]);
+    }
+
+    #[test]
+    fn it_errors_without_an_earlier_deployment() {
+        assert!(select(vec![deployment("1")], "my-worker", None).is_err());
+        assert!(select(vec![deployment("1")], "my-worker", Some("2")).is_err());
+    }
+}
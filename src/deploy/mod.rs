@@ -1,38 +1,66 @@
+pub mod history;
 mod schedule;
 mod zoned;
 mod zoneless;
+mod zones;
 
-pub use schedule::ScheduleTarget;
-pub use zoned::ZonedTarget;
+pub use schedule::{Cron, DeployedSchedule, ScheduleTarget};
+pub use zoned::{RouteTakeover, RouteUploadResult, ZonedTarget};
 pub use zoneless::ZonelessTarget;
+pub use zones::{Zone, ZoneResolver};
+
+use serde::{Deserialize, Serialize};
 
 use crate::settings::global_user::GlobalUser;
+use crate::terminal::message::{Message, StdErr};
 
 /// A set of deploy targets.
 pub type DeploymentSet = Vec<DeployTarget>;
 
-#[derive(Debug, PartialEq, Clone)]
+#[derive(Debug, PartialEq, Clone, Deserialize, Serialize)]
 pub enum DeployTarget {
     Zoned(ZonedTarget),
     Zoneless(ZonelessTarget),
+    // `workers_dev = false`: the script is taken off its workers.dev subdomain
+    WorkersDevDisabled(ZonelessTarget),
     Schedule(ScheduleTarget),
 }
 
+/// Options that change how deploy targets reconcile with what is already deployed.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct DeployOpts {
+    /// Remove routes that point at the script but are no longer configured.
+    pub prune_routes: bool,
+    /// Point routes that belong to another script at this one instead of reporting a conflict.
+    pub force_routes: bool,
+}
+
 pub fn worker(
     user: &GlobalUser,
+    script_name: &str,
     deploy_targets: &[DeployTarget],
+    opts: &DeployOpts,
 ) -> Result<DeployResults, failure::Error> {
     let mut results = DeployResults::default();
     for target in deploy_targets {
         match target {
             DeployTarget::Zoned(zoned) => {
-                let route_urls = zoned.deploy(user)?;
-                results.urls.extend(route_urls);
+                let route_results = zoned.deploy(user, script_name, opts)?;
+                results
+                    .takeovers
+                    .extend(route_results.iter().filter_map(RouteUploadResult::takeover));
+                results
+                    .urls
+                    .extend(route_results.iter().map(|r| r.to_string()));
             }
             DeployTarget::Zoneless(zoneless) => {
                 let worker_dev = zoneless.deploy(user)?;
                 results.urls.push(worker_dev);
             }
+            DeployTarget::WorkersDevDisabled(zoneless) => {
+                zoneless.disable(user)?;
+                results.workers_dev_disabled = true;
+            }
             DeployTarget::Schedule(schedule) => {
                 let schedules = schedule.deploy(user)?;
                 results.schedules.extend(schedules);
@@ -43,8 +71,149 @@ pub fn worker(
     Ok(results)
 }
 
+// Routes are pruned per zone, so a zone that a recorded publish of `script_name` had routes
+// on, but that no deploy target is on anymore, is added back without any routes of its own
+// for `--prune-routes` to clear.
+pub fn add_pruned_zones(
+    script_name: &str,
+    deploy_targets: &mut DeploymentSet,
+) -> Result<(), failure::Error> {
+    let recorded: Vec<DeployTarget> = history::list(script_name)?
+        .into_iter()
+        .flat_map(|deployment| deployment.deployments)
+        .collect();
+    let pruned = pruned_zones(&recorded, deploy_targets);
+    deploy_targets.extend(pruned);
+
+    Ok(())
+}
+
+fn pruned_zones(recorded: &[DeployTarget], deploy_targets: &[DeployTarget]) -> Vec<DeployTarget> {
+    let mut zone_ids: Vec<&str> = deploy_targets
+        .iter()
+        .filter_map(|target| match target {
+            DeployTarget::Zoned(zoned) => Some(zoned.zone_id.as_str()),
+            _ => None,
+        })
+        .collect();
+
+    let mut pruned = Vec::new();
+    for target in recorded {
+        if let DeployTarget::Zoned(zoned) = target {
+            if !zoned.routes.is_empty() && !zone_ids.contains(&zoned.zone_id.as_str()) {
+                zone_ids.push(&zoned.zone_id);
+                pruned.push(DeployTarget::Zoned(ZonedTarget {
+                    zone_id: zoned.zone_id.clone(),
+                    routes: Vec::new(),
+                    force_routes: Vec::new(),
+                }));
+            }
+        }
+    }
+
+    pruned
+}
+
+// Schedules are only published when crons are configured, so the ones an earlier
+// publish left behind have to be cleared when the deploy targets no longer have any.
+// Returns the crons that were removed. The deploy has already gone through by then, so this
+// only warns when the schedules can't be cleared.
+pub fn clear_stale_schedules(
+    user: &GlobalUser,
+    account_id: &str,
+    script_name: &str,
+    deploy_targets: &[DeployTarget],
+) -> Vec<String> {
+    match try_clear_stale_schedules(user, account_id, script_name, deploy_targets) {
+        Ok(removed) => removed,
+        Err(e) => {
+            StdErr::warn(&format!(
+                "Could not remove the schedules that are no longer in your configuration file, run `wrangler triggers clear` if {} still has any: {}",
+                script_name, e
+            ));
+            Vec::new()
+        }
+    }
+}
+
+fn try_clear_stale_schedules(
+    user: &GlobalUser,
+    account_id: &str,
+    script_name: &str,
+    deploy_targets: &[DeployTarget],
+) -> Result<Vec<String>, failure::Error> {
+    let schedules =
+        ScheduleTarget::build(account_id.to_string(), script_name.to_string(), Vec::new())?;
+    let stale = stale_schedules(user, &schedules, deploy_targets)?;
+    if !stale.is_empty() {
+        schedules.clear(user)?;
+    }
+
+    Ok(stale)
+}
+
+// The crons that `clear_stale_schedules` would remove.
+pub fn stale_schedules(
+    user: &GlobalUser,
+    schedules: &ScheduleTarget,
+    deploy_targets: &[DeployTarget],
+) -> Result<Vec<String>, failure::Error> {
+    if deploy_targets
+        .iter()
+        .any(|target| matches!(target, DeployTarget::Schedule(_)))
+    {
+        return Ok(Vec::new());
+    }
+
+    Ok(schedules
+        .fetch(user)?
+        .into_iter()
+        .map(|schedule| schedule.cron)
+        .collect())
+}
+
 #[derive(Default)]
 pub struct DeployResults {
     pub urls: Vec<String>,
     pub schedules: Vec<String>,
+    pub takeovers: Vec<RouteTakeover>,
+    pub workers_dev_disabled: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::settings::toml::Route;
+
+    fn zoned_target(zone_id: &str, patterns: &[&str]) -> DeployTarget {
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: zone_id.to_string(),
+            routes: patterns
+                .iter()
+                .map(|pattern| Route {
+                    id: None,
+                    pattern: pattern.to_string(),
+                    script: Some("my-worker".to_string()),
+                })
+                .collect(),
+            force_routes: Vec::new(),
+        })
+    }
+
+    #[test]
+    fn it_prunes_zones_that_no_longer_have_routes() {
+        let recorded = vec![
+            zoned_target("firstzoneid", &["first.com/*"]),
+            zoned_target("secondzoneid", &["second.com/*"]),
+            zoned_target("secondzoneid", &["second.com/old/*"]),
+            // a zone that was only ever pruned
+            zoned_target("thirdzoneid", &[]),
+        ];
+        let deploy_targets = vec![zoned_target("firstzoneid", &["first.com/app/*"])];
+
+        assert_eq!(
+            pruned_zones(&recorded, &deploy_targets),
+            vec![zoned_target("secondzoneid", &[])]
+        );
+    }
 }
@@ -0,0 +1,478 @@
+use std::fmt;
+use std::str::FromStr;
+
+use chrono::{DateTime, Datelike, NaiveDate, Utc, Weekday};
+
+// Every day-of-month and day-of-week combination repeats within 28 years, so an
+// expression that hasn't fired by then never will.
+const MAX_DAYS_SEARCHED: u32 = 28 * 366;
+
+const MONTH_NAMES: &[&str] = &[
+    "JAN", "FEB", "MAR", "APR", "MAY", "JUN", "JUL", "AUG", "SEP", "OCT", "NOV", "DEC",
+];
+// Workers cron triggers number the days of the week from 1 (Sunday) to 7 (Saturday).
+const DAY_NAMES: &[&str] = &["SUN", "MON", "TUE", "WED", "THU", "FRI", "SAT"];
+
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+}
+
+const MINUTE: Field = Field {
+    name: "minute",
+    min: 0,
+    max: 59,
+    names: &[],
+};
+const HOUR: Field = Field {
+    name: "hour",
+    min: 0,
+    max: 23,
+    names: &[],
+};
+const DAY_OF_MONTH: Field = Field {
+    name: "day of month",
+    min: 1,
+    max: 31,
+    names: &[],
+};
+const MONTH: Field = Field {
+    name: "month",
+    min: 1,
+    max: 12,
+    names: MONTH_NAMES,
+};
+const DAY_OF_WEEK: Field = Field {
+    name: "day of week",
+    min: 1,
+    max: 7,
+    names: DAY_NAMES,
+};
+
+/// A cron expression in the syntax accepted by Workers cron triggers:
+/// `minute hour day-of-month month day-of-week`, in UTC.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Cron {
+    expression: String,
+    minutes: u64,
+    hours: u64,
+    days_of_month: DaysOfMonth,
+    months: u64,
+    days_of_week: DaysOfWeek,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DaysOfMonth {
+    any: bool,
+    days: u64,
+    // L
+    last: bool,
+    // LW
+    last_weekday: bool,
+    // 15W
+    nearest_weekdays: Vec<u32>,
+}
+
+#[derive(Clone, Debug, Default, PartialEq)]
+struct DaysOfWeek {
+    any: bool,
+    // bit 0 is Sunday
+    days: u64,
+    // 6L, the last Friday of the month
+    last: Vec<u32>,
+    // 2#1, the first Monday of the month
+    nth: Vec<(u32, u32)>,
+}
+
+impl Cron {
+    /// The first time after `after` that the expression fires.
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let after = after.naive_utc();
+        let mut date = after.date();
+
+        for _ in 0..MAX_DAYS_SEARCHED {
+            if self.matches_date(date) {
+                for hour in (0..24).filter(|hour| has_bit(self.hours, *hour)) {
+                    for minute in (0..60).filter(|minute| has_bit(self.minutes, *minute)) {
+                        let time = date.and_hms(hour, minute, 0);
+                        if time > after {
+                            return Some(DateTime::from_utc(time, Utc));
+                        }
+                    }
+                }
+            }
+            date = date.succ();
+        }
+
+        None
+    }
+
+    /// The next `count` times after `after` that the expression fires.
+    pub fn upcoming(&self, after: DateTime<Utc>, count: usize) -> Vec<DateTime<Utc>> {
+        let mut times = Vec::new();
+        let mut after = after;
+        while times.len() < count {
+            match self.next_after(after) {
+                Some(time) => {
+                    times.push(time);
+                    after = time;
+                }
+                None => break,
+            }
+        }
+        times
+    }
+
+    fn matches_date(&self, date: NaiveDate) -> bool {
+        if !has_bit(self.months, date.month()) {
+            return false;
+        }
+
+        // like standard cron, a day matches either field when both are restricted
+        match (self.days_of_month.any, self.days_of_week.any) {
+            (true, true) => true,
+            (false, true) => self.days_of_month.matches(date),
+            (true, false) => self.days_of_week.matches(date),
+            (false, false) => self.days_of_month.matches(date) || self.days_of_week.matches(date),
+        }
+    }
+}
+
+impl DaysOfMonth {
+    fn matches(&self, date: NaiveDate) -> bool {
+        let day = date.day();
+        let last_day = last_day_of_month(date);
+
+        has_bit(self.days, day)
+            || (self.last && day == last_day)
+            || (self.last_weekday && day == nearest_weekday(date, last_day, last_day))
+            || self
+                .nearest_weekdays
+                .iter()
+                .any(|n| *n <= last_day && day == nearest_weekday(date, *n, last_day))
+    }
+}
+
+impl DaysOfWeek {
+    fn matches(&self, date: NaiveDate) -> bool {
+        let weekday = date.weekday().num_days_from_sunday();
+        let day = date.day();
+
+        has_bit(self.days, weekday)
+            || (self.last.contains(&weekday) && day + 7 > last_day_of_month(date))
+            || self
+                .nth
+                .iter()
+                .any(|(nth_weekday, n)| *nth_weekday == weekday && (day - 1) / 7 + 1 == *n)
+    }
+}
+
+impl fmt::Display for Cron {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.expression)
+    }
+}
+
+impl FromStr for Cron {
+    type Err = failure::Error;
+
+    fn from_str(expression: &str) -> Result<Self, Self::Err> {
+        let fields: Vec<&str> = expression.split_whitespace().collect();
+        if fields.len() != 5 {
+            failure::bail!(
+                "invalid cron expression \"{}\": expected 5 fields (minute hour day-of-month month day-of-week), found {}",
+                expression,
+                fields.len()
+            )
+        }
+
+        let parsed = parse_fields(&fields).map_err(|e| {
+            failure::format_err!("invalid cron expression \"{}\": {}", expression, e)
+        })?;
+
+        Ok(Cron {
+            expression: expression.to_string(),
+            ..parsed
+        })
+    }
+}
+
+fn parse_fields(fields: &[&str]) -> Result<Cron, String> {
+    Ok(Cron {
+        expression: String::new(),
+        minutes: parse_field(&MINUTE, fields[0])?,
+        hours: parse_field(&HOUR, fields[1])?,
+        days_of_month: parse_days_of_month(fields[2])?,
+        months: parse_field(&MONTH, fields[3])?,
+        days_of_week: parse_days_of_week(fields[4])?,
+    })
+}
+
+fn parse_field(field: &Field, text: &str) -> Result<u64, String> {
+    let mut bits = 0;
+    for part in text.split(',') {
+        bits |= parse_range(field, part)?;
+    }
+    Ok(bits)
+}
+
+fn parse_days_of_month(text: &str) -> Result<DaysOfMonth, String> {
+    let mut days_of_month = DaysOfMonth::default();
+    if text == "*" || text == "?" {
+        days_of_month.any = true;
+        return Ok(days_of_month);
+    }
+
+    for part in text.split(',') {
+        if part.eq_ignore_ascii_case("L") {
+            days_of_month.last = true;
+        } else if part.eq_ignore_ascii_case("LW") {
+            days_of_month.last_weekday = true;
+        } else if let Some(day) = strip_suffix_ignore_case(part, 'W') {
+            let day = parse_value(&DAY_OF_MONTH, day)?;
+            days_of_month.nearest_weekdays.push(day);
+        } else {
+            days_of_month.days |= parse_range(&DAY_OF_MONTH, part)?;
+        }
+    }
+
+    Ok(days_of_month)
+}
+
+fn parse_days_of_week(text: &str) -> Result<DaysOfWeek, String> {
+    let mut days_of_week = DaysOfWeek::default();
+    if text == "*" || text == "?" {
+        days_of_week.any = true;
+        return Ok(days_of_week);
+    }
+
+    for part in text.split(',') {
+        if part.eq_ignore_ascii_case("L") {
+            // on its own, L is the last day of the week
+            days_of_week.days |= 1 << (DAY_OF_WEEK.max - 1);
+        } else if let Some(day) = strip_suffix_ignore_case(part, 'L') {
+            days_of_week.last.push(parse_value(&DAY_OF_WEEK, day)? - 1);
+        } else if part.contains('#') {
+            let mut split = part.splitn(2, '#');
+            let day = parse_value(&DAY_OF_WEEK, split.next().unwrap_or_default())?;
+            let n = split.next().unwrap_or_default();
+            let n = match n.parse::<u32>() {
+                Ok(n) if (1..=5).contains(&n) => n,
+                _ => {
+                    return Err(format!(
+                        "\"{}\" in {}: the week after # must be 1-5",
+                        part, DAY_OF_WEEK.name
+                    ))
+                }
+            };
+            days_of_week.nth.push((day - 1, n));
+        } else {
+            // shift 1-7 down to 0-6, so that bit 0 is Sunday
+            days_of_week.days |= parse_range(&DAY_OF_WEEK, part)? >> 1;
+        }
+    }
+
+    Ok(days_of_week)
+}
+
+// `*`, `5`, `1-5`, `*/15`, `5/15` or `1-30/5`
+fn parse_range(field: &Field, part: &str) -> Result<u64, String> {
+    let mut split = part.splitn(2, '/');
+    let range = split.next().unwrap_or_default();
+    let step = match split.next() {
+        Some(step) => match step.parse::<u32>() {
+            Ok(step) if step > 0 => Some(step),
+            _ => {
+                return Err(format!(
+                    "\"{}\" in {}: the step after / must be a positive number",
+                    part, field.name
+                ))
+            }
+        },
+        None => None,
+    };
+
+    let (start, end) = if range == "*" {
+        (field.min, field.max)
+    } else if range.contains('-') {
+        let mut bounds = range.splitn(2, '-');
+        let start = parse_value(field, bounds.next().unwrap_or_default())?;
+        let end = parse_value(field, bounds.next().unwrap_or_default())?;
+        if start > end {
+            return Err(format!(
+                "\"{}\" in {}: the range must go from low to high",
+                part, field.name
+            ));
+        }
+        (start, end)
+    } else {
+        let start = parse_value(field, range)?;
+        // `5/15` starts at 5 and repeats until the end of the range
+        let end = if step.is_some() { field.max } else { start };
+        (start, end)
+    };
+
+    let mut bits = 0;
+    for value in (start..=end).step_by(step.unwrap_or(1) as usize) {
+        bits |= 1 << value;
+    }
+    Ok(bits)
+}
+
+fn parse_value(field: &Field, text: &str) -> Result<u32, String> {
+    if let Some(index) = field
+        .names
+        .iter()
+        .position(|name| name.eq_ignore_ascii_case(text))
+    {
+        return Ok(index as u32 + field.min);
+    }
+
+    let value = text.parse::<u32>().map_err(|_| {
+        format!(
+            "\"{}\" is not a valid {}",
+            if text.is_empty() { "(empty)" } else { text },
+            field.name
+        )
+    })?;
+    if value < field.min || value > field.max {
+        return Err(format!(
+            "{} {} is out of range, it must be {}-{}",
+            field.name, value, field.min, field.max
+        ));
+    }
+    Ok(value)
+}
+
+fn strip_suffix_ignore_case(text: &str, suffix: char) -> Option<&str> {
+    let mut chars = text.chars();
+    match chars.next_back() {
+        Some(last) if last.eq_ignore_ascii_case(&suffix) && !chars.as_str().is_empty() => {
+            Some(chars.as_str())
+        }
+        _ => None,
+    }
+}
+
+fn has_bit(bits: u64, value: u32) -> bool {
+    bits & (1 << value) != 0
+}
+
+fn last_day_of_month(date: NaiveDate) -> u32 {
+    let (year, month) = if date.month() == 12 {
+        (date.year() + 1, 1)
+    } else {
+        (date.year(), date.month() + 1)
+    };
+    NaiveDate::from_ymd(year, month, 1).pred().day()
+}
+
+// The weekday closest to `day` in the same month as `date`.
+fn nearest_weekday(date: NaiveDate, day: u32, last_day: u32) -> u32 {
+    match date.with_day(day).map(|date| date.weekday()) {
+        Some(Weekday::Sat) if day == 1 => day + 2,
+        Some(Weekday::Sat) => day - 1,
+        Some(Weekday::Sun) if day == last_day => day - 2,
+        Some(Weekday::Sun) => day + 1,
+        _ => day,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use chrono::TimeZone;
+
+    fn upcoming(expression: &str, after: &str, count: usize) -> Vec<String> {
+        let cron = expression.parse::<Cron>().unwrap();
+        let after = DateTime::parse_from_rfc3339(after)
+            .unwrap()
+            .with_timezone(&Utc);
+        cron.upcoming(after, count)
+            .iter()
+            .map(|time| time.format("%Y-%m-%d %H:%M %a").to_string())
+            .collect()
+    }
+
+    #[test]
+    fn it_finds_the_next_fire_times() {
+        assert_eq!(
+            upcoming("*/15 * * * *", "2021-03-01T10:07:30Z", 3),
+            vec![
+                "2021-03-01 10:15 Mon",
+                "2021-03-01 10:30 Mon",
+                "2021-03-01 10:45 Mon"
+            ]
+        );
+        assert_eq!(
+            upcoming("0 17 * * sun", "2021-03-01T00:00:00Z", 2),
+            vec!["2021-03-07 17:00 Sun", "2021-03-14 17:00 Sun"]
+        );
+        assert_eq!(
+            upcoming("30 9 1,15 JAN-MAR 1", "2021-03-01T10:00:00Z", 2),
+            vec!["2021-03-07 09:30 Sun", "2021-03-14 09:30 Sun"]
+        );
+    }
+
+    #[test]
+    fn it_supports_last_weekday_and_nth_days() {
+        assert_eq!(
+            upcoming("0 0 L * *", "2021-01-31T12:00:00Z", 2),
+            vec!["2021-02-28 00:00 Sun", "2021-03-31 00:00 Wed"]
+        );
+        assert_eq!(
+            upcoming("0 0 LW * *", "2021-01-01T00:00:00Z", 2),
+            vec!["2021-01-29 00:00 Fri", "2021-02-26 00:00 Fri"]
+        );
+        assert_eq!(
+            upcoming("0 0 1W * *", "2021-05-01T00:00:00Z", 1),
+            vec!["2021-05-03 00:00 Mon"]
+        );
+        assert_eq!(
+            upcoming("0 0 * * 6L", "2021-01-01T00:00:00Z", 2),
+            vec!["2021-01-29 00:00 Fri", "2021-02-26 00:00 Fri"]
+        );
+        assert_eq!(
+            upcoming("0 0 * * 2#1", "2021-01-01T00:00:00Z", 2),
+            vec!["2021-01-04 00:00 Mon", "2021-02-01 00:00 Mon"]
+        );
+    }
+
+    #[test]
+    fn it_stops_when_an_expression_never_fires() {
+        let cron = "0 0 30 2 *".parse::<Cron>().unwrap();
+
+        assert_eq!(cron.next_after(Utc.ymd(2021, 1, 1).and_hms(0, 0, 0)), None);
+    }
+
+    #[test]
+    fn it_rejects_invalid_expressions() {
+        let invalid = vec![
+            ("* * * *", "expected 5 fields"),
+            ("60 * * * *", "minute 60 is out of range, it must be 0-59"),
+            ("* 24 * * *", "hour 24 is out of range"),
+            ("* * 0 * *", "day of month 0 is out of range"),
+            ("* * * 13 *", "month 13 is out of range"),
+            ("* * * * 0", "day of week 0 is out of range, it must be 1-7"),
+            ("* * * FOO *", "\"FOO\" is not a valid month"),
+            ("*/0 * * * *", "the step after / must be a positive number"),
+            ("5-1 * * * *", "the range must go from low to high"),
+            ("* * * * 2#6", "the week after # must be 1-5"),
+            ("? * * * *", "\"?\" is not a valid minute"),
+        ];
+
+        for (expression, message) in invalid {
+            let error = expression.parse::<Cron>().unwrap_err().to_string();
+            assert!(
+                error.contains(message),
+                "\"{}\" should fail with \"{}\", got \"{}\"",
+                expression,
+                message,
+                error
+            );
+        }
+    }
+}
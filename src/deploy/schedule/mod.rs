@@ -0,0 +1,126 @@
+mod cron;
+
+pub use cron::Cron;
+
+use crate::http;
+use crate::settings::global_user::GlobalUser;
+
+use reqwest::StatusCode;
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ScheduleTarget {
+    pub account_id: String,
+    pub script_name: String,
+    pub crons: Vec<String>,
+}
+
+impl ScheduleTarget {
+    pub fn build(
+        account_id: String,
+        script_name: String,
+        crons: Vec<String>,
+    ) -> Result<Self, failure::Error> {
+        for cron in &crons {
+            cron.parse::<Cron>()?;
+        }
+
+        Ok(Self {
+            account_id,
+            script_name,
+            crons,
+        })
+    }
+
+    pub fn deploy(&self, user: &GlobalUser) -> Result<Vec<String>, failure::Error> {
+        log::info!("publishing schedules");
+        self.put_schedules(user, &self.crons)?;
+
+        Ok(self.crons.clone())
+    }
+
+    // Removes every schedule from the script.
+    pub fn clear(&self, user: &GlobalUser) -> Result<(), failure::Error> {
+        log::info!("clearing schedules");
+        self.put_schedules(user, &[])
+    }
+
+    // The schedules currently published for the script, whatever the configuration says.
+    pub fn fetch(&self, user: &GlobalUser) -> Result<Vec<DeployedSchedule>, failure::Error> {
+        let client = http::legacy_auth_client(user);
+
+        let res = client.get(&self.schedules_addr()).send()?;
+
+        // a script that hasn't been published yet has no schedules
+        if res.status() == StatusCode::NOT_FOUND {
+            return Ok(Vec::new());
+        }
+        if !res.status().is_success() {
+            failure::bail!(
+                "Something went wrong! Status: {}, Details {}",
+                res.status(),
+                res.text()?
+            )
+        }
+
+        let res: SchedulesResponse = res.json()?;
+        Ok(res.result.schedules)
+    }
+
+    pub fn parsed_crons(&self) -> Result<Vec<Cron>, failure::Error> {
+        self.crons.iter().map(|cron| cron.parse()).collect()
+    }
+
+    fn schedules_addr(&self) -> String {
+        format!(
+            "https://api.cloudflare.com/client/v4/accounts/{}/workers/scripts/{}/schedules",
+            self.account_id, self.script_name,
+        )
+    }
+
+    fn put_schedules(&self, user: &GlobalUser, crons: &[String]) -> Result<(), failure::Error> {
+        let client = http::legacy_auth_client(user);
+
+        log::info!("Pushing {} schedule(s)...", crons.len());
+        let res = client
+            .put(&self.schedules_addr())
+            .header("Content-Type", "application/json")
+            .body(build_schedules_request(crons))
+            .send()?;
+
+        if !res.status().is_success() {
+            failure::bail!(
+                "Something went wrong! Status: {}, Details {}",
+                res.status(),
+                res.text()?
+            )
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeployedSchedule {
+    pub cron: String,
+    pub created_on: Option<String>,
+    pub modified_on: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct SchedulesResponse {
+    result: Schedules,
+}
+
+#[derive(Deserialize)]
+struct Schedules {
+    schedules: Vec<DeployedSchedule>,
+}
+
+fn build_schedules_request(crons: &[String]) -> String {
+    let values = crons
+        .iter()
+        .map(|s| serde_json::json!({ "cron": s }))
+        .collect();
+    serde_json::Value::Array(values).to_string()
+}
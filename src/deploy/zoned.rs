@@ -1,86 +1,248 @@
 use std::fmt;
 
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
-use cloudflare::endpoints::workers::{CreateRoute, CreateRouteParams, ListRoutes};
+use cloudflare::endpoints::workers::{CreateRoute, CreateRouteParams, DeleteRoute, ListRoutes};
 use cloudflare::framework::apiclient::ApiClient;
 
+use crate::deploy::{DeployOpts, ZoneResolver};
 use crate::http;
 use crate::settings::global_user::GlobalUser;
-use crate::settings::toml::{Route, RouteConfig};
+use crate::settings::toml::{ConfigRoute, Route, RouteConfig};
 use crate::terminal::message::{Message, StdOut};
 
-#[derive(Clone, Debug, PartialEq)]
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ZonedTarget {
     pub zone_id: String,
     pub routes: Vec<Route>,
+    // patterns that should be taken over even when they point at another script
+    #[serde(default)]
+    pub force_routes: Vec<String>,
 }
 
 impl ZonedTarget {
-    pub fn build(script_name: &str, route_config: &RouteConfig) -> Result<Self, failure::Error> {
-        match route_config.zone_id.as_ref() {
-            Some(zone_id) if !zone_id.is_empty() => {
-                let new_route = |route: &String| Route {
-                    id: None,
-                    script: Some(script_name.to_string()),
-                    pattern: route.to_string(),
-                };
-                let routes: Vec<Route> = route_config
-                    .route
-                    .iter()
-                    .map(new_route)
-                    .chain(route_config.routes.iter().flatten().filter_map(|route| {
-                        if route.is_empty() {
-                            StdOut::warn("your configuration file contains an empty route");
-                            None
-                        } else {
-                            Some(new_route(route))
-                        }
-                    }))
-                    .collect();
-
-                if routes.is_empty() {
-                    failure::bail!("No routes specified");
+    // Routes are deployed per zone, so this builds a target for every zone the routes are
+    // on. A route is on the zone its table names, or else the configured `zone_id`, or else
+    // the zone its hostname belongs to.
+    pub fn build(
+        script_name: &str,
+        route_config: &RouteConfig,
+        zones: &mut ZoneResolver,
+    ) -> Result<Vec<Self>, failure::Error> {
+        let config_routes: Vec<ConfigRoute> = route_config
+            .route
+            .iter()
+            .cloned()
+            .map(ConfigRoute::Pattern)
+            .chain(route_config.routes.iter().flatten().filter_map(|route| {
+                if route.pattern().is_empty() {
+                    StdOut::warn("your configuration file contains an empty route");
+                    None
+                } else {
+                    Some(route.clone())
                 }
+            }))
+            .collect();
 
-                Ok(Self {
-                    zone_id: zone_id.to_owned(),
-                    routes,
-                })
+        if config_routes.is_empty() {
+            failure::bail!("No routes specified");
+        }
+
+        let force_routes = route_config.force_routes.clone().unwrap_or_default();
+        for pattern in &force_routes {
+            if !config_routes.iter().any(|route| route.pattern() == pattern) {
+                StdOut::warn(&format!(
+                    "`force_routes` contains {}, which is not one of your routes",
+                    pattern
+                ));
             }
-            _ => failure::bail!("field `zone_id` is required to deploy to routes"),
         }
+
+        let default_zone_id = route_config
+            .zone_id
+            .as_deref()
+            .filter(|zone_id| !zone_id.is_empty());
+
+        let mut targets: Vec<Self> = Vec::new();
+        for config_route in config_routes {
+            let pattern = config_route.pattern().to_string();
+            let zone_id = match (config_route.zone_id(), config_route.zone_name()) {
+                (Some(zone_id), _) => zone_id.to_string(),
+                (None, Some(zone_name)) => zones.resolve_name(zone_name)?.id,
+                (None, None) => match default_zone_id {
+                    Some(zone_id) => zone_id.to_string(),
+                    None => zones.resolve(&pattern)?.id,
+                },
+            };
+
+            let target = match targets.iter().position(|t| t.zone_id == zone_id) {
+                Some(i) => &mut targets[i],
+                None => {
+                    targets.push(Self {
+                        zone_id,
+                        routes: Vec::new(),
+                        force_routes: Vec::new(),
+                    });
+                    targets.last_mut().unwrap()
+                }
+            };
+            if config_route.force() || force_routes.contains(&pattern) {
+                target.force_routes.push(pattern.clone());
+            }
+            target.routes.push(Route {
+                id: None,
+                script: Some(script_name.to_string()),
+                pattern,
+            });
+        }
+
+        Ok(targets)
+    }
+
+    // Whether requests to `host` are handled by one of the routes.
+    pub fn matches_host(&self, host: &str) -> bool {
+        self.routes.iter().any(|route| route.matches_host(host))
     }
 
-    pub fn deploy(&self, user: &GlobalUser) -> Result<Vec<String>, failure::Error> {
+    pub fn deploy(
+        &self,
+        user: &GlobalUser,
+        script_name: &str,
+        opts: &DeployOpts,
+    ) -> Result<Vec<RouteUploadResult>, failure::Error> {
         log::info!("publishing to zone {}", self.zone_id);
 
-        let published_routes = publish_routes(&user, self)?;
+        publish_routes(&user, self, script_name, opts)
+    }
 
-        let display_results: Vec<String> = published_routes.iter().map(|r| r.to_string()).collect();
+    // Reports what `deploy` would do with each route without creating anything.
+    pub fn plan(
+        &self,
+        user: &GlobalUser,
+        script_name: &str,
+        opts: &DeployOpts,
+    ) -> Result<Vec<RouteUploadResult>, failure::Error> {
+        log::info!("planning routes for zone {}", self.zone_id);
 
-        Ok(display_results)
+        let existing_routes = fetch_all(user, &self.zone_id)?;
+
+        let mut planned_routes: Vec<RouteUploadResult> = self
+            .routes
+            .iter()
+            .map(|route| self.plan_route(route, &existing_routes, opts))
+            .collect();
+
+        if opts.prune_routes {
+            planned_routes.extend(
+                stale_routes(script_name, self, &existing_routes)
+                    .into_iter()
+                    .cloned()
+                    .map(RouteUploadResult::Removed),
+            );
+        }
+
+        Ok(planned_routes)
+    }
+
+    fn plan_route(
+        &self,
+        route: &Route,
+        existing_routes: &[Route],
+        opts: &DeployOpts,
+    ) -> RouteUploadResult {
+        match match_existing_route(route, existing_routes) {
+            Some(RouteUploadResult::Conflict(existing)) if self.is_forced(route, opts) => {
+                let previous_script = existing.script.clone();
+                RouteUploadResult::TakenOver((
+                    Route {
+                        script: route.script.clone(),
+                        ..existing
+                    },
+                    previous_script,
+                ))
+            }
+            Some(result) => result,
+            None => RouteUploadResult::New(route.clone()),
+        }
+    }
+
+    fn is_forced(&self, route: &Route, opts: &DeployOpts) -> bool {
+        opts.force_routes || self.force_routes.contains(&route.pattern)
+    }
+
+    // Removes every route in the zone that points at `script_name`, configured or not.
+    pub fn remove_routes(
+        &self,
+        user: &GlobalUser,
+        script_name: &str,
+    ) -> Result<Vec<Route>, failure::Error> {
+        let mut removed_routes = Vec::new();
+        for route in fetch_all(user, &self.zone_id)? {
+            if route.script.as_deref() == Some(script_name) {
+                delete(user, &self.zone_id, &route)?;
+                removed_routes.push(route);
+            }
+        }
+
+        Ok(removed_routes)
     }
 }
 
 pub fn publish_routes(
     user: &GlobalUser,
     zoned_config: &ZonedTarget,
+    script_name: &str,
+    opts: &DeployOpts,
 ) -> Result<Vec<RouteUploadResult>, failure::Error> {
     // For the moment, we'll just make this call once and make all our decisions based on the response.
     // There is a possibility of race conditions, but we just report back the results and allow the
     // user to decide how to proceed.
     let existing_routes = fetch_all(user, &zoned_config.zone_id)?;
 
-    let deployed_routes = zoned_config
+    let mut deployed_routes: Vec<RouteUploadResult> = zoned_config
         .routes
         .iter()
-        .map(|route| deploy_route(user, &zoned_config.zone_id, route, &existing_routes))
+        .map(|route| {
+            deploy_route(
+                user,
+                &zoned_config.zone_id,
+                route,
+                &existing_routes,
+                zoned_config.is_forced(route, opts),
+            )
+        })
         .collect();
 
+    if opts.prune_routes {
+        deployed_routes.extend(
+            stale_routes(script_name, zoned_config, &existing_routes)
+                .into_iter()
+                .map(|route| remove_route(user, &zoned_config.zone_id, route)),
+        );
+    }
+
     Ok(deployed_routes)
 }
 
+// Routes in the zone that still point at `script_name`, but whose pattern is no longer
+// in the configuration file.
+fn stale_routes<'a>(
+    script_name: &str,
+    zoned_config: &ZonedTarget,
+    existing_routes: &'a [Route],
+) -> Vec<&'a Route> {
+    existing_routes
+        .iter()
+        .filter(|existing_route| {
+            existing_route.script.as_deref() == Some(script_name)
+                && !zoned_config
+                    .routes
+                    .iter()
+                    .any(|route| route.pattern == existing_route.pattern)
+        })
+        .collect()
+}
+
 fn fetch_all(user: &GlobalUser, zone_identifier: &str) -> Result<Vec<Route>, failure::Error> {
     let client = http::cf_v4_client(user)?;
 
@@ -116,6 +278,73 @@ fn create(
     }
 }
 
+#[derive(Serialize)]
+struct UpdateRouteParams<'a> {
+    pattern: &'a str,
+    script: Option<&'a str>,
+}
+
+// points an existing route at our script
+fn update(
+    user: &GlobalUser,
+    zone_identifier: &str,
+    existing_route: &Route,
+    route: &Route,
+) -> Result<Route, failure::Error> {
+    let identifier = match &existing_route.id {
+        Some(id) => id,
+        None => failure::bail!("route {} has no id", existing_route.pattern),
+    };
+    let route_addr = format!(
+        "https://api.cloudflare.com/client/v4/zones/{}/workers/routes/{}",
+        zone_identifier, identifier
+    );
+
+    let client = http::legacy_auth_client(user);
+
+    log::info!("Updating your route {:#?}", &route.pattern);
+    let res = client
+        .put(&route_addr)
+        .header("Content-Type", "application/json")
+        .body(serde_json::to_string(&UpdateRouteParams {
+            pattern: &route.pattern,
+            script: route.script.as_deref(),
+        })?)
+        .send()?;
+
+    if !res.status().is_success() {
+        failure::bail!(
+            "Something went wrong! Status: {}, Details {}",
+            res.status(),
+            res.text()?
+        )
+    }
+
+    Ok(Route {
+        id: existing_route.id.clone(),
+        script: route.script.clone(),
+        pattern: route.pattern.clone(),
+    })
+}
+
+fn delete(user: &GlobalUser, zone_identifier: &str, route: &Route) -> Result<(), failure::Error> {
+    let identifier = match &route.id {
+        Some(id) => id,
+        None => failure::bail!("route {} has no id", route.pattern),
+    };
+
+    let client = http::cf_v4_client(user)?;
+
+    log::info!("Removing route {:#?}", &route.pattern);
+    match client.request(&DeleteRoute {
+        zone_identifier,
+        identifier,
+    }) {
+        Ok(_) => Ok(()),
+        Err(e) => failure::bail!("{}", http::format_error(e, None)),
+    }
+}
+
 // TODO: improve this error message to reference wrangler route commands
 fn routes_error_help(error_code: u16) -> &'static str {
     match error_code {
@@ -135,9 +364,30 @@ pub enum RouteUploadResult {
     Same(Route),
     Conflict(Route),
     New(Route),
+    Removed(Route),
+    // the route, along with the script it pointed at before we took it over
+    TakenOver((Route, Option<String>)),
     Error((Route, String)),
 }
 
+impl RouteUploadResult {
+    pub fn takeover(&self) -> Option<RouteTakeover> {
+        match self {
+            RouteUploadResult::TakenOver((route, previous_script)) => Some(RouteTakeover {
+                pattern: route.pattern.clone(),
+                previous_script: previous_script.clone(),
+            }),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RouteTakeover {
+    pub pattern: String,
+    pub previous_script: Option<String>,
+}
+
 impl fmt::Display for RouteUploadResult {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         match self {
@@ -149,8 +399,15 @@ impl fmt::Display for RouteUploadResult {
                 route.script.as_ref().unwrap_or(&"null worker".to_string())
             ),
             RouteUploadResult::New(route) => write!(f, "{} => created", route.pattern),
+            RouteUploadResult::Removed(route) => write!(f, "{} => removed", route.pattern),
+            RouteUploadResult::TakenOver((route, previous_script)) => write!(
+                f,
+                "{} => reassigned from {}",
+                route.pattern,
+                previous_script.as_deref().unwrap_or("null worker")
+            ),
             RouteUploadResult::Error((route, message)) => {
-                write!(f, "{} => creation failed: {}", route.pattern, message)
+                write!(f, "{} => {}", route.pattern, message)
             }
         }
     }
@@ -161,39 +418,129 @@ fn deploy_route(
     zone_id: &str,
     route: &Route,
     existing_routes: &[Route],
+    force: bool,
 ) -> RouteUploadResult {
+    match match_existing_route(route, existing_routes) {
+        Some(RouteUploadResult::Conflict(existing)) if force => {
+            return take_over(user, zone_id, route, &existing)
+        }
+        Some(result) => return result,
+        None => {}
+    }
+
+    // if none of the existing routes match this one, we should create a new route
+    match create(user, zone_id, &route) {
+        // we want to show the new route along with its id
+        Ok(created) => RouteUploadResult::New(created),
+        // if there is an error, we want to know which route triggered it
+        Err(e) => RouteUploadResult::Error((
+            Route {
+                id: None,
+                script: route.script.clone(),
+                pattern: route.pattern.clone(),
+            },
+            format!("creation failed: {}", e),
+        )),
+    }
+}
+
+fn take_over(
+    user: &GlobalUser,
+    zone_id: &str,
+    route: &Route,
+    existing_route: &Route,
+) -> RouteUploadResult {
+    match update(user, zone_id, existing_route, route) {
+        Ok(updated) => RouteUploadResult::TakenOver((updated, existing_route.script.clone())),
+        Err(e) => RouteUploadResult::Error((route.clone(), format!("takeover failed: {}", e))),
+    }
+}
+
+fn remove_route(user: &GlobalUser, zone_id: &str, route: &Route) -> RouteUploadResult {
+    match delete(user, zone_id, route) {
+        Ok(()) => RouteUploadResult::Removed(route.clone()),
+        Err(e) => RouteUploadResult::Error((route.clone(), format!("removal failed: {}", e))),
+    }
+}
+
+fn match_existing_route(route: &Route, existing_routes: &[Route]) -> Option<RouteUploadResult> {
     for existing_route in existing_routes {
         if route.pattern == existing_route.pattern {
             // if the route is already assigned, we don't need to call the api.
             // if the script names match, it's a no-op.
             if route.script == existing_route.script {
-                return RouteUploadResult::Same(Route {
+                return Some(RouteUploadResult::Same(Route {
                     id: existing_route.id.clone(),
                     script: existing_route.script.clone(),
                     pattern: existing_route.pattern.clone(),
-                });
+                }));
             }
             // if the script names do not match, we want to know which script is conflicting.
-            return RouteUploadResult::Conflict(Route {
+            return Some(RouteUploadResult::Conflict(Route {
                 id: existing_route.id.clone(),
                 script: existing_route.script.clone(),
                 pattern: existing_route.pattern.clone(),
-            });
+            }));
         }
     }
 
-    // if none of the existing routes match this one, we should create a new route
-    match create(user, zone_id, &route) {
-        // we want to show the new route along with its id
-        Ok(created) => RouteUploadResult::New(created),
-        // if there is an error, we want to know which route triggered it
-        Err(e) => RouteUploadResult::Error((
-            Route {
-                id: None,
-                script: route.script.clone(),
-                pattern: route.pattern.clone(),
-            },
-            e.to_string(),
-        )),
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn route(pattern: &str, script: Option<&str>) -> Route {
+        Route {
+            id: Some(pattern.to_string()),
+            pattern: pattern.to_string(),
+            script: script.map(String::from),
+        }
+    }
+
+    fn zoned_target(patterns: &[&str]) -> ZonedTarget {
+        ZonedTarget {
+            zone_id: "samplezoneid".to_string(),
+            routes: patterns
+                .iter()
+                .map(|pattern| Route {
+                    id: None,
+                    pattern: pattern.to_string(),
+                    script: Some("my-worker".to_string()),
+                })
+                .collect(),
+            force_routes: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn it_finds_routes_that_are_no_longer_configured() {
+        let existing_routes = vec![
+            route("example.com/*", Some("my-worker")),
+            route("example.com/old/*", Some("my-worker")),
+            route("example.com/other/*", Some("other-worker")),
+            route("example.com/static/*", None),
+        ];
+
+        let stale = stale_routes(
+            "my-worker",
+            &zoned_target(&["example.com/*"]),
+            &existing_routes,
+        );
+
+        assert_eq!(stale, vec![&existing_routes[1]]);
+    }
+
+    #[test]
+    fn it_finds_every_route_of_the_script_when_none_are_configured() {
+        let existing_routes = vec![
+            route("example.com/*", Some("my-worker")),
+            route("example.com/other/*", Some("other-worker")),
+        ];
+
+        let stale = stale_routes("my-worker", &zoned_target(&[]), &existing_routes);
+
+        assert_eq!(stale, vec![&existing_routes[0]]);
     }
 }
@@ -3,7 +3,9 @@ use crate::http;
 use crate::settings::global_user::GlobalUser;
 use crate::settings::toml::RouteConfig;
 
-#[derive(Clone, Debug, PartialEq)]
+use serde::{Deserialize, Serialize};
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
 pub struct ZonelessTarget {
     pub account_id: String,
     pub script_name: String,
@@ -41,7 +43,7 @@ impl ZonelessTarget {
         let res = client
             .post(&sd_worker_addr)
             .header("Content-type", "application/json")
-            .body(build_subdomain_request())
+            .body(build_subdomain_request(true))
             .send()?;
 
         if !res.status().is_success() {
@@ -56,8 +58,35 @@ impl ZonelessTarget {
 
         Ok(deploy_address)
     }
+
+    // Stops serving the script on the workers.dev subdomain.
+    pub fn disable(&self, user: &GlobalUser) -> Result<(), failure::Error> {
+        log::info!("removing from workers.dev subdomain");
+        let sd_worker_addr = format!(
+            "https://api.cloudflare.com/client/v4/accounts/{}/workers/scripts/{}/subdomain",
+            self.account_id, self.script_name,
+        );
+
+        let client = http::legacy_auth_client(user);
+
+        let res = client
+            .post(&sd_worker_addr)
+            .header("Content-type", "application/json")
+            .body(build_subdomain_request(false))
+            .send()?;
+
+        if !res.status().is_success() {
+            failure::bail!(
+                "Something went wrong! Status: {}, Details {}",
+                res.status(),
+                res.text()?
+            )
+        }
+
+        Ok(())
+    }
 }
 
-fn build_subdomain_request() -> String {
-    serde_json::json!({ "enabled": true }).to_string()
+fn build_subdomain_request(enabled: bool) -> String {
+    serde_json::json!({ "enabled": enabled }).to_string()
 }
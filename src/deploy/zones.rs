@@ -0,0 +1,282 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::http;
+use crate::settings::get_project_state_dir;
+use crate::settings::global_user::GlobalUser;
+use crate::terminal::message::{Message, StdErr};
+
+const ZONE_CACHE_FILE_NAME: &str = "zones.json";
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Zone {
+    pub id: String,
+    pub name: String,
+}
+
+#[derive(Deserialize)]
+struct ZonesResponse {
+    result: Vec<Zone>,
+}
+
+// the zones of each account, by hostname
+type ZoneCache = BTreeMap<String, BTreeMap<String, Zone>>;
+
+/// Finds the zone of an account that the hostname of a route pattern belongs to. Zones that
+/// were already looked up are cached in the project state, so the zones API is only asked
+/// about new hostnames.
+#[derive(Default)]
+pub struct ZoneResolver {
+    account_id: String,
+    // hostname -> zone of the account, loaded from the project state on first use
+    cache: Option<BTreeMap<String, Zone>>,
+    user: Option<GlobalUser>,
+    // only resolve from the cache, without calling the API
+    offline: bool,
+}
+
+impl ZoneResolver {
+    // Resolves hostnames from the cache, and looks up the ones that aren't in it.
+    pub fn new(account_id: &str) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            ..Self::default()
+        }
+    }
+
+    // Resolves hostnames from the cache only, so that reading the configuration never needs
+    // the network or credentials.
+    pub fn cached(account_id: &str) -> Self {
+        Self {
+            account_id: account_id.to_string(),
+            offline: true,
+            ..Self::default()
+        }
+    }
+
+    // Resolves hostnames from `zones` only.
+    #[cfg(test)]
+    pub fn offline(zones: Vec<(&str, Zone)>) -> Self {
+        Self {
+            cache: Some(
+                zones
+                    .into_iter()
+                    .map(|(hostname, zone)| (hostname.to_string(), zone))
+                    .collect(),
+            ),
+            offline: true,
+            ..Self::default()
+        }
+    }
+
+    pub fn resolve(&mut self, pattern: &str) -> Result<Zone, failure::Error> {
+        let hostname = match pattern_hostname(pattern) {
+            Some(hostname) => hostname,
+            None => failure::bail!("Could not find a hostname in the route {}", pattern),
+        };
+
+        if let Some(zone) = self.cache()?.get(hostname) {
+            log::info!(
+                "found zone {} for {} in the zone cache",
+                zone.name,
+                hostname
+            );
+            return Ok(zone.clone());
+        }
+
+        if !self.offline {
+            // a hostname can belong to a zone for any of its parent domains, and the most
+            // specific one wins
+            for zone_name in zone_names(hostname) {
+                if let Some(zone) = self.fetch_zone(zone_name)? {
+                    log::info!("found zone {} for {}", zone.name, hostname);
+                    self.cache()?.insert(hostname.to_string(), zone.clone());
+                    self.save_cache();
+                    return Ok(zone);
+                }
+            }
+        }
+
+        if self.offline {
+            failure::bail!(
+                "The zone that {} belongs to hasn't been looked up yet. Add a `zone_id` to your configuration file, or run `wrangler publish` first.",
+                hostname
+            )
+        }
+        failure::bail!(
+            "Could not find the zone that {} belongs to on your account. Add a `zone_id` to your configuration file to deploy this route.",
+            hostname
+        )
+    }
+
+    pub fn resolve_name(&mut self, zone_name: &str) -> Result<Zone, failure::Error> {
+        // the apex hostname of a zone always belongs to it, so zones are cached by name too
+        if let Some(zone) = self.cache()?.get(zone_name) {
+            if zone.name == zone_name {
+                return Ok(zone.clone());
+            }
+        }
+
+        if !self.offline {
+            if let Some(zone) = self.fetch_zone(zone_name)? {
+                self.cache()?.insert(zone_name.to_string(), zone.clone());
+                self.save_cache();
+                return Ok(zone);
+            }
+        }
+
+        failure::bail!("Could not find the zone {} on your account", zone_name)
+    }
+
+    fn fetch_zone(&mut self, zone_name: &str) -> Result<Option<Zone>, failure::Error> {
+        if self.user.is_none() {
+            self.user = Some(GlobalUser::new()?);
+        }
+        let client = http::legacy_auth_client(self.user.as_ref().unwrap());
+
+        log::info!("looking up zone {}", zone_name);
+        let mut query = vec![("name", zone_name)];
+        if !self.account_id.is_empty() {
+            query.push(("account.id", self.account_id.as_str()));
+        }
+        let res = client
+            .get("https://api.cloudflare.com/client/v4/zones")
+            .query(&query)
+            .send()?;
+
+        if !res.status().is_success() {
+            failure::bail!(
+                "Something went wrong! Status: {}, Details {}",
+                res.status(),
+                res.text()?
+            )
+        }
+
+        let res: ZonesResponse = res.json()?;
+        Ok(res.result.into_iter().next())
+    }
+
+    fn cache(&mut self) -> Result<&mut BTreeMap<String, Zone>, failure::Error> {
+        if self.cache.is_none() {
+            let cache = read_cache()?.remove(&self.account_id).unwrap_or_default();
+            self.cache = Some(cache);
+        }
+
+        Ok(self.cache.as_mut().unwrap())
+    }
+
+    // The cache only saves lookups, so failing to write it shouldn't fail the command.
+    fn save_cache(&self) {
+        let write = || -> Result<(), failure::Error> {
+            let mut zone_cache = read_cache()?;
+            if let Some(cache) = &self.cache {
+                zone_cache.insert(self.account_id.clone(), cache.clone());
+            }
+
+            let cache_path = cache_path()?;
+            if let Some(state_dir) = cache_path.parent() {
+                fs::create_dir_all(state_dir)?;
+            }
+            fs::write(cache_path, serde_json::to_string_pretty(&zone_cache)?)?;
+            Ok(())
+        };
+
+        if let Err(e) = write() {
+            StdErr::warn(&format!("Could not save the zone cache: {}", e));
+        }
+    }
+}
+
+fn cache_path() -> Result<PathBuf, failure::Error> {
+    Ok(get_project_state_dir()?.join(ZONE_CACHE_FILE_NAME))
+}
+
+fn read_cache() -> Result<ZoneCache, failure::Error> {
+    let cache_path = cache_path()?;
+    if !cache_path.is_file() {
+        return Ok(ZoneCache::new());
+    }
+
+    let cache_json = fs::read_to_string(&cache_path)?;
+    Ok(serde_json::from_str(&cache_json).unwrap_or_else(|e| {
+        log::info!("ignoring unreadable zone cache: {}", e);
+        ZoneCache::new()
+    }))
+}
+
+// The hostname a route pattern such as `https://*.example.com/*` applies to.
+fn pattern_hostname(pattern: &str) -> Option<&str> {
+    let pattern = pattern
+        .trim_start_matches("https://")
+        .trim_start_matches("http://");
+    let host = pattern.split('/').next()?;
+    let hostname = host
+        .split(':')
+        .next()?
+        .trim_start_matches('*')
+        .trim_start_matches('.');
+
+    if hostname.is_empty() || hostname.contains('*') {
+        None
+    } else {
+        Some(hostname)
+    }
+}
+
+// Every zone `hostname` could belong to, most specific first. Top level domains are left out.
+fn zone_names(hostname: &str) -> Vec<&str> {
+    let mut zone_names = vec![hostname];
+    let mut rest = hostname;
+    while let Some(dot) = rest.find('.') {
+        rest = &rest[dot + 1..];
+        if !rest.contains('.') {
+            break;
+        }
+        zone_names.push(rest);
+    }
+
+    zone_names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_finds_the_hostname_of_a_pattern() {
+        assert_eq!(pattern_hostname("example.com/*"), Some("example.com"));
+        assert_eq!(
+            pattern_hostname("https://*.blog.example.com/posts*"),
+            Some("blog.example.com")
+        );
+        assert_eq!(pattern_hostname("*example.com:8080/*"), Some("example.com"));
+        assert_eq!(pattern_hostname("*/*"), None);
+        assert_eq!(pattern_hostname("www.*.example.com/*"), None);
+    }
+
+    #[test]
+    fn it_lists_candidate_zones_most_specific_first() {
+        assert_eq!(
+            zone_names("a.blog.example.co"),
+            vec!["a.blog.example.co", "blog.example.co", "example.co"]
+        );
+        assert_eq!(zone_names("example.com"), vec!["example.com"]);
+    }
+
+    #[test]
+    fn it_resolves_zones_from_the_cache() {
+        let zone = Zone {
+            id: "zoneid".to_string(),
+            name: "example.com".to_string(),
+        };
+        let mut zones = ZoneResolver::offline(vec![("blog.example.com", zone.clone())]);
+
+        assert_eq!(zones.resolve("blog.example.com/*").unwrap(), zone);
+        assert!(zones.resolve("example.com/*").is_err());
+        // a hostname that is cached is not necessarily the name of its zone
+        assert!(zones.resolve_name("blog.example.com").is_err());
+    }
+}
@@ -37,6 +37,7 @@ pub struct EnvConfig {
     pub workers_dev: Option<bool>,
     pub route: Option<&'static str>,
     pub routes: Option<Vec<&'static str>>,
+    pub force_routes: Option<Vec<&'static str>>,
     pub zone_id: Option<&'static str>,
     pub webpack_config: Option<&'static str>,
     pub private: Option<bool>,
@@ -97,6 +98,7 @@ pub struct WranglerToml {
     pub workers_dev: Option<bool>,
     pub route: Option<&'static str>,
     pub routes: Option<Vec<&'static str>>,
+    pub force_routes: Option<Vec<&'static str>>,
     pub zone_id: Option<&'static str>,
     pub webpack_config: Option<&'static str>,
     pub private: Option<bool>,
@@ -22,6 +22,19 @@ pub fn featured_legacy_auth_client(user: &GlobalUser, feature: Feature) -> Clien
     get_client(user, Some(feature))
 }
 
+// For requests that can take longer than the default timeout, like KV bulk writes.
+pub fn legacy_auth_client_with_timeout(user: &GlobalUser, timeout: Duration) -> Client {
+    let mut headers = headers(None);
+    add_auth_headers(&mut headers, user);
+
+    builder()
+        .timeout(timeout)
+        .default_headers(headers)
+        .redirect(Policy::none())
+        .build()
+        .expect("could not create authenticated http client")
+}
+
 fn get_client(user: &GlobalUser, feature: Option<Feature>) -> Client {
     let mut headers = headers(feature);
     add_auth_headers(&mut headers, user);
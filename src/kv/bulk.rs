@@ -1,18 +1,23 @@
+use std::collections::VecDeque;
+use std::env;
+use std::fs;
+use std::io::{Read, Seek, SeekFrom};
+use std::path::PathBuf;
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
 use std::time::Duration;
 
 use indicatif::ProgressBar;
+use reqwest::blocking::RequestBuilder;
+use reqwest::header::RETRY_AFTER;
+use reqwest::StatusCode;
 
-use cloudflare::endpoints::workerskv::delete_bulk::DeleteBulk;
-use cloudflare::endpoints::workerskv::write_bulk::KeyValuePair;
-use cloudflare::endpoints::workerskv::write_bulk::WriteBulk;
-use cloudflare::framework::apiclient::ApiClient;
-use cloudflare::framework::auth::Credentials;
-use cloudflare::framework::{Environment, HttpApiClient, HttpApiClientConfig};
+use serde::{Deserialize, Serialize};
 
-use crate::commands::kv::format_error;
-use crate::http::feature::headers;
+use crate::http;
 use crate::settings::global_user::GlobalUser;
 use crate::settings::toml::Target;
+use crate::sites::Encoding;
 
 const API_MAX_PAIRS: usize = 10000;
 // The consts below are halved from the API's true capacity to help avoid
@@ -20,19 +25,33 @@ const API_MAX_PAIRS: usize = 10000;
 pub const BATCH_KEY_MAX: usize = API_MAX_PAIRS / 2;
 const UPLOAD_MAX_SIZE: usize = 50 * 1024 * 1024;
 
-// Create a special API client that has a longer timeout than usual, given that KV operations
-// can be lengthy if payloads are large.
-fn bulk_api_client(user: &GlobalUser) -> Result<HttpApiClient, failure::Error> {
-    let config = HttpApiClientConfig {
-        http_timeout: Duration::from_secs(5 * 60),
-        default_headers: headers(None),
-    };
+// How many batches are in flight at once, unless set with WRANGLER_KV_CONCURRENCY.
+const DEFAULT_CONCURRENCY: usize = 4;
+// Batches that fail with a 429, a 5xx or a network error are sent again, waiting twice as
+// long after every attempt unless the API says how long to wait.
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(30);
+// A batch is given up on right away when the API asks to wait longer than this before sending
+// it again, rather than holding up the whole upload.
+const MAX_RETRY_AFTER: Duration = Duration::from_secs(10 * 60);
+// KV operations can be lengthy if payloads are large.
+const BULK_TIMEOUT: Duration = Duration::from_secs(5 * 60);
 
-    HttpApiClient::new(
-        Credentials::from(user.to_owned()),
-        config,
-        Environment::Production,
-    )
+/// A key to write with `put`: the `KeyValuePair` of the cloudflare crate, plus the metadata
+/// Workers KV can store alongside each value.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct KeyValuePair {
+    pub key: String,
+    pub value: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub expiration_ttl: Option<i64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub base64: Option<bool>,
 }
 
 pub fn put(
@@ -42,24 +61,80 @@ pub fn put(
     pairs: Vec<KeyValuePair>,
     progress_bar: &Option<ProgressBar>,
 ) -> Result<(), failure::Error> {
-    let client = bulk_api_client(user)?;
-
-    for b in batch_keys_values(pairs) {
-        match client.request(&WriteBulk {
-            account_identifier: &target.account_id,
-            namespace_identifier: namespace_id,
-            bulk_key_value_pairs: b.to_owned(),
-        }) {
-            Ok(_) => {}
-            Err(e) => failure::bail!("{}", format_error(e)),
-        }
+    let client = http::legacy_auth_client_with_timeout(user, BULK_TIMEOUT);
+    let addr = bulk_addr(target, namespace_id);
 
-        if let Some(pb) = &progress_bar {
-            pb.inc(b.len() as u64);
-        }
-    }
+    send_batches(
+        "written to",
+        batch_keys_values(pairs),
+        |pair: &KeyValuePair| pair.key.clone(),
+        move |batch: &[KeyValuePair]| send_request(client.put(&addr).json(batch)),
+        |_: &[KeyValuePair]| {},
+        progress_bar,
+    )
+}
 
-    Ok(())
+/// A key whose value is the contents of a file, or `size` bytes of it from `offset` on. The file
+/// is only read when the batch it is in gets sent, so the files being written don't have to fit
+/// in memory at once.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyFile {
+    pub key: String,
+    pub path: PathBuf,
+    pub size: u64,
+    pub offset: Option<u64>,
+    // the encoding the contents are compressed with before they are written, if any
+    pub encoding: Option<Encoding>,
+    pub metadata: Option<serde_json::Value>,
+}
+
+// Like `put`, with each value read from a file and base64 encoded right before its batch is sent.
+// `on_uploaded` is called with every batch that was written.
+pub fn put_files(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+    files: Vec<KeyFile>,
+    progress_bar: &Option<ProgressBar>,
+    on_uploaded: &mut dyn FnMut(&[KeyFile]),
+) -> Result<(), failure::Error> {
+    let client = http::legacy_auth_client_with_timeout(user, BULK_TIMEOUT);
+    let addr = bulk_addr(target, namespace_id);
+
+    send_batches(
+        "written to",
+        batch_files(files),
+        |file: &KeyFile| file.key.clone(),
+        move |batch: &[KeyFile]| {
+            let mut pairs = Vec::with_capacity(batch.len());
+            for file in batch {
+                let mut value = read_value(file).map_err(|e| {
+                    BatchError::Permanent(format!("Could not read {}: {}", file.path.display(), e))
+                })?;
+                if let Some(encoding) = file.encoding {
+                    value = encoding.compress(&value).map_err(|e| {
+                        BatchError::Permanent(format!(
+                            "Could not compress {} with {}: {}",
+                            file.path.display(),
+                            encoding,
+                            e
+                        ))
+                    })?;
+                }
+                pairs.push(KeyValuePair {
+                    key: file.key.clone(),
+                    value: base64::encode(&value),
+                    expiration: None,
+                    expiration_ttl: None,
+                    metadata: file.metadata.clone(),
+                    base64: Some(true),
+                });
+            }
+            send_request(client.put(&addr).json(&pairs))
+        },
+        on_uploaded,
+        progress_bar,
+    )
 }
 
 pub fn delete(
@@ -69,24 +144,238 @@ pub fn delete(
     keys: Vec<String>,
     progress_bar: &Option<ProgressBar>,
 ) -> Result<(), failure::Error> {
-    let client = bulk_api_client(user)?;
-
-    for b in batch_keys(keys) {
-        match client.request(&DeleteBulk {
-            account_identifier: &target.account_id,
-            namespace_identifier: namespace_id,
-            bulk_keys: b.to_owned(),
-        }) {
-            Ok(_) => {}
-            Err(e) => failure::bail!("{}", format_error(e)),
+    let client = http::legacy_auth_client_with_timeout(user, BULK_TIMEOUT);
+    let addr = bulk_addr(target, namespace_id);
+
+    send_batches(
+        "deleted from",
+        batch_keys(keys),
+        String::clone,
+        move |batch: &[String]| send_request(client.delete(&addr).json(batch)),
+        |_: &[String]| {},
+        progress_bar,
+    )
+}
+
+fn bulk_addr(target: &Target, namespace_id: &str) -> String {
+    format!(
+        "https://api.cloudflare.com/client/v4/accounts/{}/storage/kv/namespaces/{}/bulk",
+        target.account_id, namespace_id
+    )
+}
+
+#[derive(Debug)]
+enum BatchError {
+    // worth sending again, after `retry_after` if the API asked for it
+    Transient {
+        retry_after: Option<Duration>,
+        details: String,
+    },
+    Permanent(String),
+}
+
+impl BatchError {
+    fn details(&self) -> &str {
+        match self {
+            BatchError::Transient { details, .. } => details,
+            BatchError::Permanent(details) => details,
         }
+    }
+}
 
-        if let Some(pb) = &progress_bar {
-            pb.inc(b.len() as u64);
+fn send_request(request: RequestBuilder) -> Result<(), BatchError> {
+    let res = match request.send() {
+        Ok(res) => res,
+        Err(e) if e.is_timeout() || e.is_connect() => {
+            return Err(BatchError::Transient {
+                retry_after: None,
+                details: e.to_string(),
+            })
+        }
+        Err(e) => return Err(BatchError::Permanent(e.to_string())),
+    };
+
+    let status = res.status();
+    if status.is_success() {
+        return Ok(());
+    }
+
+    let retry_after = res
+        .headers()
+        .get(RETRY_AFTER)
+        .and_then(|value| value.to_str().ok())
+        .and_then(parse_retry_after);
+    let details = format!(
+        "Something went wrong! Status: {}, Details {}",
+        status,
+        res.text().unwrap_or_default()
+    );
+
+    if status == StatusCode::TOO_MANY_REQUESTS || status.is_server_error() {
+        Err(BatchError::Transient {
+            retry_after,
+            details,
+        })
+    } else {
+        Err(BatchError::Permanent(details))
+    }
+}
+
+// The seconds a Retry-After header asks to wait.
+fn parse_retry_after(value: &str) -> Option<Duration> {
+    let seconds = value.trim().parse::<u64>().ok()?;
+    Some(Duration::from_secs(seconds))
+}
+
+// The keys of a batch that was given up on, and why.
+struct FailedBatch {
+    keys: Vec<String>,
+    details: String,
+}
+
+// Sends the batches from a few threads at once. A batch that fails for good doesn't stop the
+// others, and the keys of every such batch are listed in the returned error, which says they
+// could not be `operation` Workers KV.
+fn send_batches<T, K, S, O>(
+    operation: &str,
+    batches: Vec<Vec<T>>,
+    key: K,
+    send: S,
+    mut on_sent: O,
+    progress_bar: &Option<ProgressBar>,
+) -> Result<(), failure::Error>
+where
+    T: Send + 'static,
+    K: Fn(&T) -> String,
+    O: FnMut(&[T]),
+    S: Fn(&[T]) -> Result<(), BatchError> + Send + Sync + 'static,
+{
+    let total_keys: usize = batches.iter().map(Vec::len).sum();
+    let workers = concurrency().min(batches.len());
+    let queue = Arc::new(Mutex::new(batches.into_iter().collect::<VecDeque<_>>()));
+    let send = Arc::new(send);
+    let (results_tx, results_rx) = mpsc::channel();
+
+    let mut handles = Vec::new();
+    for _ in 0..workers {
+        let queue = Arc::clone(&queue);
+        let send = Arc::clone(&send);
+        let results_tx = results_tx.clone();
+        handles.push(thread::spawn(move || loop {
+            // the lock is released before the batch is sent
+            let batch = match queue.lock().unwrap().pop_front() {
+                Some(batch) => batch,
+                None => break,
+            };
+            let result = send_with_retries(&*send, &batch);
+            if results_tx.send((batch, result)).is_err() {
+                break;
+            }
+        }));
+    }
+    // so that receiving stops once every worker is done
+    drop(results_tx);
+
+    let mut failed_batches = Vec::new();
+    for (batch, result) in results_rx {
+        match result {
+            Ok(()) => on_sent(&batch),
+            Err(details) => {
+                failed_batches.push(FailedBatch {
+                    keys: batch.iter().map(&key).collect(),
+                    details,
+                });
+                if let Some(pb) = progress_bar {
+                    pb.set_message(&format!("{} batch(es) failed", failed_batches.len()));
+                }
+            }
+        }
+        if let Some(pb) = progress_bar {
+            pb.inc(batch.len() as u64);
+        }
+    }
+    for handle in handles {
+        if handle.join().is_err() {
+            failure::bail!("A thread sending KV batches panicked")
+        }
+    }
+
+    if failed_batches.is_empty() {
+        Ok(())
+    } else {
+        failure::bail!("{}", failure_report(operation, &failed_batches, total_keys))
+    }
+}
+
+fn send_with_retries<T, S>(send: &S, batch: &[T]) -> Result<(), String>
+where
+    S: Fn(&[T]) -> Result<(), BatchError>,
+{
+    let mut attempt = 1;
+    loop {
+        match send(batch) {
+            Ok(()) => return Ok(()),
+            Err(BatchError::Transient {
+                retry_after: Some(retry_after),
+                details,
+            }) if retry_after > MAX_RETRY_AFTER => {
+                return Err(format!(
+                    "Workers KV asked to wait {} seconds before sending these keys again, which is longer than {} seconds. Try again later.\n{}",
+                    retry_after.as_secs(),
+                    MAX_RETRY_AFTER.as_secs(),
+                    details
+                ))
+            }
+            Err(BatchError::Transient {
+                retry_after,
+                details,
+            }) if attempt < MAX_ATTEMPTS => {
+                let wait = retry_after.unwrap_or_else(|| backoff(attempt));
+                log::info!(
+                    "sending a batch of {} keys failed (attempt {} of {}), retrying in {:?}: {}",
+                    batch.len(),
+                    attempt,
+                    MAX_ATTEMPTS,
+                    wait,
+                    details
+                );
+                thread::sleep(wait);
+                attempt += 1;
+            }
+            Err(e) => return Err(e.details().to_string()),
         }
     }
+}
+
+// How long to wait before sending a batch again after `attempt` attempts.
+fn backoff(attempt: u32) -> Duration {
+    let backoff = INITIAL_BACKOFF * 2u32.saturating_pow(attempt.saturating_sub(1));
+    backoff.min(MAX_BACKOFF)
+}
+
+fn concurrency() -> usize {
+    env::var("WRANGLER_KV_CONCURRENCY")
+        .ok()
+        .and_then(|concurrency| concurrency.parse::<usize>().ok())
+        .filter(|concurrency| *concurrency > 0)
+        .unwrap_or(DEFAULT_CONCURRENCY)
+}
+
+fn failure_report(operation: &str, failed_batches: &[FailedBatch], total_keys: usize) -> String {
+    let failed_keys: usize = failed_batches.iter().map(|batch| batch.keys.len()).sum();
+    let mut report = format!(
+        "{} of {} keys could not be {} Workers KV",
+        failed_keys, total_keys, operation
+    );
+    for batch in failed_batches {
+        report.push_str(&format!(
+            "\n\n{}\n {}",
+            batch.details,
+            batch.keys.join("\n ")
+        ));
+    }
 
-    Ok(())
+    report
 }
 
 fn batch_keys_values(mut pairs: Vec<KeyValuePair>) -> Vec<Vec<KeyValuePair>> {
@@ -128,6 +417,50 @@ fn batch_keys_values(mut pairs: Vec<KeyValuePair>) -> Vec<Vec<KeyValuePair>> {
     batches
 }
 
+// Batches files the same way as key-value pairs, by the size their values will have once
+// they're base64 encoded.
+fn batch_files(files: Vec<KeyFile>) -> Vec<Vec<KeyFile>> {
+    let mut batches = Vec::new();
+    let mut batch: Vec<KeyFile> = Vec::new();
+    let mut batch_bytes = 0;
+
+    for file in files {
+        let file_bytes = file.key.len() + encoded_len(file.size);
+        if !batch.is_empty()
+            && (batch.len() + 1 > BATCH_KEY_MAX || batch_bytes + file_bytes > UPLOAD_MAX_SIZE)
+        {
+            batches.push(batch);
+            batch = Vec::new();
+            batch_bytes = 0;
+        }
+        batch_bytes += file_bytes;
+        batch.push(file);
+    }
+    if !batch.is_empty() {
+        batches.push(batch);
+    }
+
+    batches
+}
+
+fn read_value(file: &KeyFile) -> Result<Vec<u8>, std::io::Error> {
+    match file.offset {
+        Some(offset) => {
+            let mut reader = fs::File::open(&file.path)?;
+            reader.seek(SeekFrom::Start(offset))?;
+            let mut value = Vec::with_capacity(file.size as usize);
+            reader.take(file.size).read_to_end(&mut value)?;
+            Ok(value)
+        }
+        None => fs::read(&file.path),
+    }
+}
+
+// The length of the base64 encoding of `size` bytes.
+fn encoded_len(size: u64) -> usize {
+    ((size as usize + 2) / 3) * 4
+}
+
 fn batch_keys(mut keys: Vec<String>) -> Vec<Vec<String>> {
     let mut batches = Vec::new();
     while !keys.is_empty() {
@@ -142,3 +475,150 @@ fn batch_keys(mut keys: Vec<String>) -> Vec<Vec<String>> {
 
     batches
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    fn transient() -> BatchError {
+        BatchError::Transient {
+            retry_after: Some(Duration::from_millis(0)),
+            details: "Status: 429".to_string(),
+        }
+    }
+
+    fn key_file(key: &str, size: u64) -> KeyFile {
+        KeyFile {
+            key: key.to_string(),
+            path: PathBuf::from(key),
+            size,
+            offset: None,
+            encoding: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn it_batches_files_by_their_encoded_size() {
+        // 30MB of files is 40MB once encoded, so two of them don't fit in one batch
+        let big = 30 * 1024 * 1024;
+        let files = vec![key_file("a", big), key_file("b", 10), key_file("c", big)];
+
+        let batches: Vec<Vec<&str>> = batch_files(files)
+            .iter()
+            .map(|batch| batch.iter().map(|file| file.key.as_str()).collect())
+            .collect();
+
+        assert_eq!(batches, vec![vec!["a", "b"], vec!["c"]]);
+        assert_eq!(encoded_len(3), 4);
+        assert_eq!(encoded_len(4), 8);
+        assert_eq!(encoded_len(0), 0);
+    }
+
+    #[test]
+    fn it_backs_off_exponentially_up_to_a_limit() {
+        assert_eq!(backoff(1), Duration::from_millis(500));
+        assert_eq!(backoff(2), Duration::from_secs(1));
+        assert_eq!(backoff(4), Duration::from_secs(4));
+        assert_eq!(backoff(10), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn it_waits_as_long_as_retry_after_asks() {
+        assert_eq!(parse_retry_after(" 2 "), Some(Duration::from_secs(2)));
+        assert_eq!(parse_retry_after("120"), Some(Duration::from_secs(120)));
+        assert_eq!(parse_retry_after("Wed, 21 Oct 2015 07:28:00 GMT"), None);
+    }
+
+    #[test]
+    fn it_gives_up_when_retry_after_is_too_long() {
+        let attempts = AtomicU32::new(0);
+        let send = |_: &[String]| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(BatchError::Transient {
+                retry_after: Some(Duration::from_secs(3600)),
+                details: "Status: 429".to_string(),
+            })
+        };
+
+        let error = send_with_retries(&send, &["key".to_string()]).unwrap_err();
+        assert!(error.starts_with("Workers KV asked to wait 3600 seconds"));
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn it_retries_transient_failures() {
+        let attempts = AtomicU32::new(0);
+        let send = |_: &[String]| {
+            if attempts.fetch_add(1, Ordering::SeqCst) < 2 {
+                Err(transient())
+            } else {
+                Ok(())
+            }
+        };
+
+        assert!(send_with_retries(&send, &["key".to_string()]).is_ok());
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn it_gives_up_on_permanent_failures_and_after_the_last_attempt() {
+        let attempts = AtomicU32::new(0);
+        let send = |_: &[String]| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(BatchError::Permanent("Status: 400".to_string()))
+        };
+        assert_eq!(
+            send_with_retries(&send, &["key".to_string()]),
+            Err("Status: 400".to_string())
+        );
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+
+        let attempts = AtomicU32::new(0);
+        let send = |_: &[String]| {
+            attempts.fetch_add(1, Ordering::SeqCst);
+            Err(transient())
+        };
+        assert!(send_with_retries(&send, &["key".to_string()]).is_err());
+        assert_eq!(attempts.load(Ordering::SeqCst), MAX_ATTEMPTS);
+    }
+
+    #[test]
+    fn it_reports_the_keys_of_failed_batches() {
+        let batches = vec![
+            vec!["a".to_string(), "b".to_string()],
+            vec!["bad".to_string()],
+            vec!["c".to_string()],
+        ];
+        let send = |batch: &[String]| {
+            if batch.iter().any(|key| key == "bad") {
+                Err(BatchError::Permanent("Status: 413".to_string()))
+            } else {
+                Ok(())
+            }
+        };
+
+        let progress_bar = Some(ProgressBar::hidden());
+        let mut sent = Vec::new();
+        let error = send_batches(
+            "deleted from",
+            batches,
+            String::clone,
+            send,
+            |batch: &[String]| sent.extend_from_slice(batch),
+            &progress_bar,
+        )
+        .unwrap_err()
+        .to_string();
+
+        assert_eq!(
+            error,
+            "1 of 4 keys could not be deleted from Workers KV\n\nStatus: 413\n bad"
+        );
+        assert_eq!(progress_bar.unwrap().position(), 4);
+        sent.sort();
+        assert_eq!(sent, vec!["a", "b", "c"]);
+    }
+}
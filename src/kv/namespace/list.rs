@@ -9,13 +9,12 @@ use cloudflare::framework::response::ApiSuccess;
 use serde::Deserialize;
 
 use crate::commands::kv;
-use crate::settings::toml::Target;
 
 const MAX_NAMESPACES_PER_PAGE: u32 = 100;
 
 pub fn list(
     client: &impl ApiClient,
-    target: &Target,
+    account_id: &str,
 ) -> Result<Vec<WorkersKvNamespace>, failure::Error> {
     let mut namespaces: Vec<WorkersKvNamespace> = Vec::new();
     let mut all_namespaces_added = false;
@@ -27,7 +26,7 @@ pub fn list(
         };
 
         match client.request(&ListNamespaces {
-            account_identifier: &target.account_id,
+            account_identifier: account_id,
             params,
         }) {
             Ok(response) => {
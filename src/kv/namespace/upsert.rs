@@ -3,7 +3,6 @@ use cloudflare::framework::response::ApiFailure;
 
 use crate::http;
 use crate::settings::global_user::GlobalUser;
-use crate::settings::toml::Target;
 
 use super::create;
 use super::list;
@@ -14,12 +13,12 @@ pub enum UpsertedNamespace {
 }
 
 pub fn upsert(
-    target: &Target,
+    account_id: &str,
     user: &GlobalUser,
     title: String,
 ) -> Result<UpsertedNamespace, failure::Error> {
     let client = http::cf_v4_client(user)?;
-    let response = create(&client, &target.account_id, &title);
+    let response = create(&client, account_id, &title);
 
     match response {
         Ok(success) => Ok(UpsertedNamespace::Created(success.result)),
@@ -28,7 +27,7 @@ pub fn upsert(
                 if api_errors.errors.iter().any(|e| e.code == 10014) {
                     log::info!("Namespace {} already exists.", title);
 
-                    match list(&client, target)?
+                    match list(&client, account_id)?
                         .iter()
                         .find(|ns| ns.title == title) {
                         Some(namespace) => Ok(UpsertedNamespace::Reused(namespace.to_owned())),
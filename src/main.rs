@@ -5,7 +5,7 @@ extern crate tokio;
 
 use std::convert::TryFrom;
 use std::env;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use clap::{App, AppSettings, Arg, ArgGroup, SubCommand};
@@ -14,6 +14,7 @@ use url::Url;
 
 use wrangler::commands;
 use wrangler::commands::kv::key::{parse_metadata, KVMetaData};
+use wrangler::deploy::ZoneResolver;
 use wrangler::installer;
 use wrangler::preview::{HttpMethod, PreviewOpt};
 use wrangler::settings;
@@ -127,7 +128,7 @@ fn run() -> Result<(), failure::Error> {
                 .setting(AppSettings::SubcommandRequiredElseHelp)
                 .subcommand(
                     SubCommand::with_name("create")
-                        .about("Create a new namespace")
+                        .about("Create a new namespace and add it to your configuration file")
                         .arg(environment_arg.clone())
                         .arg(
                             Arg::with_name("binding")
@@ -320,25 +321,51 @@ fn run() -> Result<(), failure::Error> {
         .subcommand(
             SubCommand::with_name("route")
                 .about(&*format!(
-                    "{} List or delete worker routes.",
+                    "{} List, create or delete worker routes.",
                     emoji::ROUTE
                 ))
                 .arg(silent_verbose_arg.clone())
                 .setting(AppSettings::SubcommandRequiredElseHelp)
                 .subcommand(
                     SubCommand::with_name("list")
-                        .about("List all routes associated with a zone (outputs json)")
+                        .about("List the routes on the zones of your project, and the scripts they belong to")
                         .arg(environment_arg.clone())
                         .arg(wrangler_file.clone())
                         .arg(silent_verbose_arg.clone())
+                        .arg(
+                            Arg::with_name("output")
+                            .short("o")
+                            .long("output")
+                            .takes_value(true)
+                            .possible_value("json")
+                        )
+                )
+                .subcommand(
+                    SubCommand::with_name("create")
+                        .arg(environment_arg.clone())
+                        .about("Create a route")
+                        .arg(
+                            Arg::with_name("pattern")
+                            .help("the pattern of the route, e.g. example.com/*")
+                            .required(true)
+                            .index(1)
+                        )
+                        .arg(
+                            Arg::with_name("script")
+                            .help("the script the route points at. defaults to the worker of the environment")
+                            .long("script")
+                            .takes_value(true)
+                        )
+                        .arg(silent_verbose_arg.clone())
+                        .arg(wrangler_file.clone())
                 )
                 .subcommand(
                     SubCommand::with_name("delete")
                         .arg(environment_arg.clone())
-                        .about("Delete a route by id")
+                        .about("Delete a route by pattern")
                         .arg(
-                            Arg::with_name("route_id")
-                            .help("the id associated with the route you want to delete (find using `wrangler route list`)")
+                            Arg::with_name("pattern")
+                            .help("the pattern (or id) of the route you want to delete (find using `wrangler route list`)")
                             .required(true)
                             .index(1)
                         )
@@ -573,8 +600,228 @@ fn run() -> Result<(), failure::Error> {
                     .long("output")
                     .takes_value(true)
                     .possible_value("json")
+                )
+                .arg(
+                    Arg::with_name("dry-run")
+                        .help("build your worker and report what would be published, without publishing anything")
+                        .long("dry-run")
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::with_name("outdir")
+                        .help("write the parts of the upload form to this directory (requires --dry-run)")
+                        .long("outdir")
+                        .takes_value(true)
+                        .value_name("DIR")
+                        .requires("dry-run")
+                )
+                .arg(
+                    Arg::with_name("prune-routes")
+                        .help("remove routes that point at this worker but are no longer in your configuration file")
+                        .long("prune-routes")
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::with_name("force-routes")
+                        .help("point routes that belong to another worker at this one")
+                        .long("force-routes")
+                        .takes_value(false)
+                )
+                .arg(
+                    Arg::with_name("resume")
+                        .help("continue the site upload of the last interrupted publish, skipping the files it already uploaded")
+                        .long("resume")
+                        .takes_value(false)
+                        .conflicts_with("dry-run")
+                )
+                .arg(
+                    Arg::with_name("preview-branch")
+                        .help("publish to a script of its own for this branch, on workers.dev and with the preview ids of your KV namespaces")
+                        .long("preview-branch")
+                        .takes_value(true)
+                        .value_name("BRANCH")
+                        .conflicts_with_all(&["prune-routes", "force-routes"])
                 ),
         )
+        .subcommand(
+            SubCommand::with_name("preview-branch")
+                .about(&*format!(
+                    "{} Manage the scripts published with `wrangler publish --preview-branch`",
+                    emoji::UP
+                ))
+                .arg(silent_verbose_arg.clone())
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("cleanup")
+                        .about("Delete the script and Workers Sites namespace of a preview branch")
+                        .arg(
+                            Arg::with_name("branch")
+                            .help("the branch to clean up")
+                            .index(1)
+                        )
+                        .arg(
+                            Arg::with_name("older-than")
+                            .help("clean up every preview branch that wasn't published within this long, e.g. 7d")
+                            .long("older-than")
+                            .takes_value(true)
+                            .value_name("AGE")
+                        )
+                        .group(
+                            ArgGroup::with_name("preview-branches")
+                                .args(&["branch", "older-than"])
+                                .required(true)
+                        )
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(silent_verbose_arg.clone())
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("rollback")
+                .about(&*format!(
+                    "{} Publish an earlier deployment of your Worker again",
+                    emoji::UP
+                ))
+                .arg(wrangler_file.clone())
+                .arg(environment_arg.clone())
+                .arg(
+                    Arg::with_name("to")
+                        .help("the id of the deployment to roll back to (find using `wrangler deployments list`). Defaults to the deployment before the latest one")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("ID")
+                )
+                .arg(silent_verbose_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("promote")
+                .about(&*format!(
+                    "{} Publish the latest deployment of one environment to another, without building again",
+                    emoji::UP
+                ))
+                .arg(wrangler_file.clone())
+                .arg(
+                    Arg::with_name("from")
+                        .help("the environment whose latest deployment is promoted")
+                        .long("from")
+                        .takes_value(true)
+                        .value_name("ENV")
+                        .required(true)
+                )
+                .arg(
+                    Arg::with_name("to")
+                        .help("the environment to publish it to")
+                        .long("to")
+                        .takes_value(true)
+                        .value_name("ENV")
+                        .required(true)
+                )
+                .arg(silent_verbose_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("delete")
+                .about(&*format!(
+                    "{} Delete your Worker along with its routes, schedules, workers.dev subdomain and Workers Sites namespaces",
+                    emoji::WARN
+                ))
+                .arg(wrangler_file.clone())
+                .arg(environment_arg.clone())
+                .arg(
+                    Arg::with_name("yes")
+                        .help("delete without asking for confirmation")
+                        .short("y")
+                        .long("yes")
+                        .takes_value(false)
+                )
+                .arg(silent_verbose_arg.clone()),
+        )
+        .subcommand(
+            SubCommand::with_name("deployments")
+                .about(&*format!(
+                    "{} List the recorded deployments of your Worker",
+                    emoji::FILES
+                ))
+                .arg(silent_verbose_arg.clone())
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List every deployment recorded by `wrangler publish`, newest first")
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(silent_verbose_arg.clone())
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("site")
+                .about(&*format!(
+                    "{} Manage the files of your Workers Site",
+                    emoji::FILES
+                ))
+                .arg(silent_verbose_arg.clone())
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("gc")
+                        .about("Delete the files that none of the last `retain_versions` publishes use")
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(silent_verbose_arg.clone())
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("triggers")
+                .about(&*format!(
+                    "{} Inspect and manage the cron triggers of your Worker",
+                    emoji::FILES
+                ))
+                .arg(silent_verbose_arg.clone())
+                .setting(AppSettings::SubcommandRequiredElseHelp)
+                .subcommand(
+                    SubCommand::with_name("preview")
+                        .about("Print the next times, in UTC, that each configured cron fires")
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(
+                            Arg::with_name("count")
+                                .help("how many fire times to print for each cron")
+                                .short("n")
+                                .long("count")
+                                .takes_value(true)
+                                .value_name("COUNT")
+                                .default_value("5")
+                        )
+                        .arg(silent_verbose_arg.clone())
+                )
+                .subcommand(
+                    SubCommand::with_name("list")
+                        .about("List the schedules currently deployed for your Worker")
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(silent_verbose_arg.clone())
+                )
+                .subcommand(
+                    SubCommand::with_name("clear")
+                        .about("Remove every schedule deployed for your Worker")
+                        .arg(environment_arg.clone())
+                        .arg(wrangler_file.clone())
+                        .arg(
+                            Arg::with_name("yes")
+                                .help("remove without asking for confirmation")
+                                .short("y")
+                                .long("yes")
+                                .takes_value(false)
+                        )
+                        .arg(silent_verbose_arg.clone())
+                )
+        )
+        .subcommand(
+            SubCommand::with_name("provision")
+                .about(&*format!(
+                    "{} Create the KV namespaces your configuration file is missing ids for",
+                    emoji::FILES
+                ))
+                .arg(wrangler_file.clone())
+                .arg(silent_verbose_arg.clone()),
+        )
         .subcommand(
             SubCommand::with_name("config")
                 .about(&*format!(
@@ -809,9 +1056,10 @@ fn run() -> Result<(), failure::Error> {
         }
 
         let env = matches.value_of("env");
-        let deployments = manifest.get_deployments(env)?;
         is_preview = true;
         let target = manifest.get_target(env, is_preview)?;
+        let deployments =
+            manifest.get_deployments_with_zones(env, &mut ZoneResolver::new(&target.account_id))?;
         let user = settings::global_user::GlobalUser::new().ok();
         let verbose = matches.is_present("verbose");
 
@@ -858,13 +1106,186 @@ fn run() -> Result<(), failure::Error> {
         );
         let manifest = settings::toml::Manifest::new(config_path)?;
         let env = matches.value_of("env");
-        let mut target = manifest.get_target(env, is_preview)?;
-        let deploy_config = manifest.get_deployments(env)?;
-        if matches.is_present("output") && matches.value_of("output") == Some("json") {
-            commands::publish(&user, &mut target, deploy_config, Output::Json)?;
+        let opts = commands::PublishOpts {
+            dry_run: matches.is_present("dry-run"),
+            outdir: matches.value_of("outdir").map(PathBuf::from),
+            prune_routes: matches.is_present("prune-routes"),
+            force_routes: matches.is_present("force-routes"),
+            resume: matches.is_present("resume"),
+        };
+        let out = if matches.is_present("output") && matches.value_of("output") == Some("json") {
+            Output::Json
+        } else {
+            Output::PlainText
+        };
+        if let Some(branch) = matches.value_of("preview-branch") {
+            commands::preview_branch::publish(&user, &manifest, env, branch, out, opts)?;
         } else {
-            commands::publish(&user, &mut target, deploy_config, Output::PlainText)?;
+            let mut target = manifest.get_target(env, is_preview)?;
+            let deploy_config = manifest
+                .get_deployments_with_zones(env, &mut ZoneResolver::new(&target.account_id))?;
+            commands::publish(&user, &mut target, deploy_config, out, opts)?;
+        }
+    } else if let Some(preview_branch_matches) = matches.subcommand_matches("preview-branch") {
+        log::info!("Getting User settings");
+        let user = settings::global_user::GlobalUser::new()?;
+
+        log::info!("Getting project settings");
+        let (_, cleanup_matches) = preview_branch_matches.subcommand();
+        let cleanup_matches = cleanup_matches.unwrap();
+        let config_path = Path::new(
+            cleanup_matches
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+        let env = cleanup_matches.value_of("env");
+        let older_than = match cleanup_matches.value_of("older-than") {
+            Some(age) => Some(commands::preview_branch::parse_age(age)?),
+            None => None,
+        };
+
+        commands::preview_branch::cleanup(
+            &user,
+            &manifest,
+            env,
+            cleanup_matches.value_of("branch"),
+            older_than,
+        )?;
+    } else if let Some(matches) = matches.subcommand_matches("rollback") {
+        log::info!("Getting User settings");
+        let user = settings::global_user::GlobalUser::new()?;
+
+        log::info!("Getting project settings");
+        let config_path = Path::new(
+            matches
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+        let env = matches.value_of("env");
+        let target = manifest.get_target(env, is_preview)?;
+
+        commands::rollback(&user, &target, matches.value_of("to"))?;
+    } else if let Some(matches) = matches.subcommand_matches("promote") {
+        log::info!("Getting User settings");
+        let user = settings::global_user::GlobalUser::new()?;
+
+        log::info!("Getting project settings");
+        let config_path = Path::new(
+            matches
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+        let from = matches.value_of("from");
+        let to = matches.value_of("to");
+        // the latest deployment of the source is what gets promoted, its target is only needed
+        // to find the files of its Workers Site the same way they were published
+        let source_target = manifest.get_target(from, is_preview)?;
+        let mut target = manifest.get_target(to, is_preview)?;
+        let deployments =
+            manifest.get_deployments_with_zones(to, &mut ZoneResolver::new(&target.account_id))?;
+
+        commands::promote(&user, &source_target, &mut target, &deployments)?;
+    } else if let Some(matches) = matches.subcommand_matches("delete") {
+        log::info!("Getting User settings");
+        let user = settings::global_user::GlobalUser::new()?;
+
+        log::info!("Getting project settings");
+        let config_path = Path::new(
+            matches
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+        let env = matches.value_of("env");
+        let target = manifest.get_target(env, is_preview)?;
+        let deployments =
+            manifest.get_deployments_with_zones(env, &mut ZoneResolver::new(&target.account_id))?;
+
+        commands::delete(&user, &target, &deployments, matches.is_present("yes"))?;
+    } else if let Some(deployments_matches) = matches.subcommand_matches("deployments") {
+        let (subcommand, subcommand_matches) = deployments_matches.subcommand();
+        let config_path = Path::new(
+            subcommand_matches
+                .unwrap()
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+
+        match (subcommand, subcommand_matches) {
+            ("list", Some(list_matches)) => {
+                let env = list_matches.value_of("env");
+                let target = manifest.get_target(env, is_preview)?;
+                commands::deployments::list(&target)?;
+            }
+            _ => unreachable!(),
         }
+    } else if let Some(site_matches) = matches.subcommand_matches("site") {
+        let (subcommand, subcommand_matches) = site_matches.subcommand();
+        let config_path = Path::new(
+            subcommand_matches
+                .unwrap()
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+
+        match (subcommand, subcommand_matches) {
+            ("gc", Some(gc_matches)) => {
+                let user = settings::global_user::GlobalUser::new()?;
+                let target = manifest.get_target(gc_matches.value_of("env"), is_preview)?;
+                commands::site::gc(&user, &target)?;
+            }
+            _ => unreachable!(),
+        }
+    } else if let Some(triggers_matches) = matches.subcommand_matches("triggers") {
+        let (subcommand, subcommand_matches) = triggers_matches.subcommand();
+        let config_path = Path::new(
+            subcommand_matches
+                .unwrap()
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+
+        match (subcommand, subcommand_matches) {
+            ("preview", Some(preview_matches)) => {
+                let env = preview_matches.value_of("env");
+                let schedule = manifest.get_schedule(env)?;
+                let count = match preview_matches.value_of("count").unwrap().parse() {
+                    Ok(count) => count,
+                    Err(_) => failure::bail!("--count expects a number"),
+                };
+                commands::triggers::preview(schedule.as_ref(), count)?;
+            }
+            ("list", Some(list_matches)) => {
+                let user = settings::global_user::GlobalUser::new()?;
+                let target = manifest.get_target(list_matches.value_of("env"), is_preview)?;
+                commands::triggers::list(&user, &target)?;
+            }
+            ("clear", Some(clear_matches)) => {
+                let user = settings::global_user::GlobalUser::new()?;
+                let target = manifest.get_target(clear_matches.value_of("env"), is_preview)?;
+                commands::triggers::clear(&user, &target, clear_matches.is_present("yes"))?;
+            }
+            _ => unreachable!(),
+        }
+    } else if let Some(matches) = matches.subcommand_matches("provision") {
+        log::info!("Getting User settings");
+        let user = settings::global_user::GlobalUser::new()?;
+
+        log::info!("Getting project settings");
+        let config_path = Path::new(
+            matches
+                .value_of("config")
+                .unwrap_or(commands::DEFAULT_CONFIG_PATH),
+        );
+        let manifest = settings::toml::Manifest::new(config_path)?;
+
+        commands::provision(config_path, &manifest, &user)?;
     } else if let Some(matches) = matches.subcommand_matches("subdomain") {
         log::info!("Getting project settings");
         let config_path = Path::new(
@@ -898,29 +1319,23 @@ fn run() -> Result<(), failure::Error> {
         let manifest = settings::toml::Manifest::new(config_path)?;
         let env = subcommand_matches.unwrap().value_of("env");
 
-        let env_zone_id = if let Some(environment) = manifest.get_environment(env)? {
-            environment.zone_id.as_ref()
-        } else {
-            None
-        };
-
-        let zone_id: Result<String, failure::Error> = if let Some(zone_id) = env_zone_id {
-            Ok(zone_id.to_string())
-        } else if let Some(zone_id) = manifest.zone_id {
-            Ok(zone_id)
-        } else {
-            failure::bail!(
-                "You must specify a zone_id in your configuration file to use `wrangler route` commands."
-            )
-        };
-
         match (subcommand, subcommand_matches) {
-            ("list", Some(_)) => {
-                commands::route::list(zone_id?, &user)?;
+            ("list", Some(list_matches)) => {
+                let out = if list_matches.value_of("output") == Some("json") {
+                    Output::Json
+                } else {
+                    Output::PlainText
+                };
+                commands::route::list(&manifest, env, &user, out)?;
+            }
+            ("create", Some(create_matches)) => {
+                let pattern = create_matches.value_of("pattern").unwrap();
+                let script = create_matches.value_of("script");
+                commands::route::create(&manifest, env, &user, pattern, script)?;
             }
             ("delete", Some(delete_matches)) => {
-                let route_id = delete_matches.value_of("route_id").unwrap();
-                commands::route::delete(zone_id?, &user, route_id)?;
+                let pattern = delete_matches.value_of("pattern").unwrap();
+                commands::route::delete(&manifest, env, &user, pattern)?;
             }
             _ => unreachable!(),
         }
@@ -979,7 +1394,14 @@ fn run() -> Result<(), failure::Error> {
                 is_preview = create_matches.is_present("preview");
                 let env = create_matches.value_of("env");
                 let binding = create_matches.value_of("binding").unwrap();
-                commands::kv::namespace::create(&manifest, is_preview, env, &user, binding)?;
+                commands::kv::namespace::create(
+                    config_path,
+                    &manifest,
+                    is_preview,
+                    env,
+                    &user,
+                    binding,
+                )?;
             }
             ("delete", Some(delete_matches)) => {
                 is_preview = delete_matches.is_present("preview");
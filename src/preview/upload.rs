@@ -70,7 +70,14 @@ pub fn upload(
                         StdOut::info("Uploading updated files...");
                     }
 
-                    bulk::put(target, user, &site_namespace.id, to_upload, &None)?;
+                    bulk::put_files(
+                        target,
+                        user,
+                        &site_namespace.id,
+                        to_upload,
+                        &None,
+                        &mut |_| {},
+                    )?;
 
                     let preview = authenticated_upload(&client, &target, Some(asset_manifest))?;
                     if !to_delete.is_empty() {
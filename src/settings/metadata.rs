@@ -1,9 +1,13 @@
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 
 use crate::settings::binding::Binding;
 
-#[derive(Serialize, Debug)]
+#[derive(Deserialize, Serialize, Debug)]
 pub struct Metadata {
-    pub body_part: String,
+    // service-worker scripts name their script part, module workers name their main module
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body_part: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub main_module: Option<String>,
     pub bindings: Vec<Binding>,
 }
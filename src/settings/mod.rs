@@ -3,7 +3,9 @@ mod environment;
 mod global_config;
 pub mod global_user;
 pub mod metadata;
+mod project_state;
 pub mod toml;
 
 pub use environment::{Environment, QueryEnvironment};
 pub use global_config::{get_global_config_path, get_wrangler_home_dir, DEFAULT_CONFIG_FILE_NAME};
+pub use project_state::{get_project_state_dir, PROJECT_STATE_DIR_NAME};
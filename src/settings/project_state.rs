@@ -0,0 +1,13 @@
+use std::env;
+use std::path::PathBuf;
+
+pub const PROJECT_STATE_DIR_NAME: &str = ".wrangler";
+
+// Wrangler keeps state about the project it is run from (deployment history and the like)
+// in the `.wrangler` directory of the current directory, which paths in the configuration
+// file are relative to as well.
+pub fn get_project_state_dir() -> Result<PathBuf, failure::Error> {
+    let state_dir = env::current_dir()?.join(PROJECT_STATE_DIR_NAME);
+    log::info!("Using project state directory: {}", state_dir.display());
+    Ok(state_dir)
+}
@@ -0,0 +1,173 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use serde::{Deserialize, Serialize};
+
+const UPLOAD_DIR: &str = "dist";
+const SERVICE_WORKER_MAIN: &str = "worker.js";
+const WATCH_DIR: &str = "src";
+
+// A custom build: `command` is run through the shell in `cwd`, and whatever it
+// writes to the `upload` output path is what gets published.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct Builder {
+    pub command: Option<String>,
+    #[serde(default = "project_root")]
+    pub cwd: PathBuf,
+    #[serde(default = "default_watch_dir")]
+    pub watch_dir: PathBuf,
+    #[serde(default)]
+    pub upload: UploadFormat,
+}
+
+impl Default for Builder {
+    fn default() -> Builder {
+        Builder {
+            command: None,
+            cwd: project_root(),
+            watch_dir: default_watch_dir(),
+            upload: UploadFormat::default(),
+        }
+    }
+}
+
+impl Builder {
+    pub fn build_command(&self) -> Option<(&str, Command)> {
+        let command_str = self.command.as_deref()?;
+
+        let mut c = if cfg!(target_os = "windows") {
+            let mut c = Command::new("cmd");
+            c.arg("/C");
+            c
+        } else {
+            let mut c = Command::new("sh");
+            c.arg("-c");
+            c
+        };
+        c.arg(command_str);
+        c.current_dir(&self.cwd);
+
+        Some((command_str, c))
+    }
+
+    // The file the build produces: the script for service workers, the main module for modules.
+    pub fn artifact_path(&self) -> PathBuf {
+        self.upload.artifact_path()
+    }
+
+    pub fn verify_artifact(&self) -> Result<(), failure::Error> {
+        let artifact_path = self.artifact_path();
+        if !artifact_path.is_file() {
+            failure::bail!(
+                "Could not find {}, which [build.upload] in your wrangler.toml says the build produces",
+                artifact_path.display()
+            )
+        }
+
+        Ok(())
+    }
+}
+
+fn project_root() -> PathBuf {
+    PathBuf::from(".")
+}
+
+fn default_watch_dir() -> PathBuf {
+    PathBuf::from(WATCH_DIR)
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(tag = "format", rename_all = "kebab-case")]
+pub enum UploadFormat {
+    ServiceWorker {
+        // the script, relative to `dir`
+        #[serde(default = "default_service_worker_main")]
+        main: PathBuf,
+        #[serde(default = "default_upload_dir")]
+        dir: PathBuf,
+    },
+    Modules {
+        // the entry point, relative to `dir`
+        main: PathBuf,
+        #[serde(default = "default_upload_dir")]
+        dir: PathBuf,
+        #[serde(default)]
+        rules: Vec<ModuleRule>,
+    },
+}
+
+impl Default for UploadFormat {
+    fn default() -> UploadFormat {
+        UploadFormat::ServiceWorker {
+            main: default_service_worker_main(),
+            dir: default_upload_dir(),
+        }
+    }
+}
+
+impl UploadFormat {
+    pub fn artifact_path(&self) -> PathBuf {
+        match self {
+            UploadFormat::ServiceWorker { main, dir } => dir.join(main),
+            UploadFormat::Modules { main, dir, .. } => dir.join(main),
+        }
+    }
+}
+
+fn default_service_worker_main() -> PathBuf {
+    PathBuf::from(SERVICE_WORKER_MAIN)
+}
+
+fn default_upload_dir() -> PathBuf {
+    PathBuf::from(UPLOAD_DIR)
+}
+
+// Files in the modules directory that match one of `globs` are uploaded as modules of `type`.
+// Unless `fallthrough` is set, later rules (including the default ones) for the same type are ignored.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct ModuleRule {
+    pub globs: Vec<String>,
+    #[serde(rename = "type")]
+    pub module_type: ModuleType,
+    #[serde(default)]
+    pub fallthrough: bool,
+}
+
+impl ModuleRule {
+    pub fn defaults() -> Vec<ModuleRule> {
+        vec![
+            ModuleRule {
+                globs: vec!["**/*.mjs".to_string()],
+                module_type: ModuleType::ESModule,
+                fallthrough: false,
+            },
+            ModuleRule {
+                globs: vec!["**/*.js".to_string(), "**/*.cjs".to_string()],
+                module_type: ModuleType::CommonJS,
+                fallthrough: false,
+            },
+        ]
+    }
+}
+
+#[derive(Clone, Copy, Debug, Deserialize, PartialEq, Serialize)]
+pub enum ModuleType {
+    ESModule,
+    CommonJS,
+    CompiledWasm,
+    Text,
+    Data,
+}
+
+impl ModuleType {
+    pub fn content_type(&self) -> &'static str {
+        match self {
+            ModuleType::ESModule => "application/javascript+module",
+            ModuleType::CommonJS => "application/javascript",
+            ModuleType::CompiledWasm => "application/wasm",
+            ModuleType::Text => "text/plain",
+            ModuleType::Data => "application/octet-stream",
+        }
+    }
+}
@@ -0,0 +1,242 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use toml_edit::{value, Array, Document, InlineTable, Item, Table, TableLike, Value};
+
+use crate::settings::toml::Manifest;
+
+/// Edits a wrangler.toml in place, keeping its comments, formatting and key order.
+pub struct ManifestEditor {
+    config_path: PathBuf,
+    document: Document,
+}
+
+impl ManifestEditor {
+    pub fn open(config_path: &Path) -> Result<Self, failure::Error> {
+        let contents = fs::read_to_string(config_path)?;
+        Self::parse(config_path, &contents)
+    }
+
+    fn parse(config_path: &Path, contents: &str) -> Result<Self, failure::Error> {
+        let document = contents.parse::<Document>().map_err(|e| {
+            failure::format_err!("could not parse {}: {}", config_path.display(), e)
+        })?;
+
+        Ok(ManifestEditor {
+            config_path: config_path.to_path_buf(),
+            document,
+        })
+    }
+
+    // Writes the edited configuration back, as long as it is still a valid Manifest.
+    pub fn save(&self) -> Result<(), failure::Error> {
+        let contents = self.document.to_string();
+        Manifest::from_str(&contents)?;
+        fs::write(&self.config_path, contents)?;
+
+        Ok(())
+    }
+
+    // The top level table, or the `[env.<name>]` table when an environment is given.
+    // The environment table is added if it doesn't exist yet.
+    pub fn table_mut(&mut self, env: Option<&str>) -> Result<&mut dyn TableLike, failure::Error> {
+        let root = self.document.as_table_mut();
+        let env = match env {
+            Some(env) => env,
+            None => return Ok(root),
+        };
+
+        let environments = root.entry("env").or_insert_with(|| {
+            let mut environments = Table::new();
+            environments.set_implicit(true);
+            Item::Table(environments)
+        });
+        let environments = match environments.as_table_like_mut() {
+            Some(environments) => environments,
+            None => failure::bail!("env must be a table"),
+        };
+
+        match environments
+            .entry(env)
+            .or_insert_with(|| Item::Table(Table::new()))
+            .as_table_like_mut()
+        {
+            Some(environment) => Ok(environment),
+            None => failure::bail!("env.{} must be a table", env),
+        }
+    }
+
+    // Sets the id (or preview_id) of the kv namespace with the given binding, adding the
+    // namespace to kv_namespaces if it isn't there yet.
+    pub fn upsert_kv_namespace(
+        &mut self,
+        env: Option<&str>,
+        binding: &str,
+        id: &str,
+        is_preview: bool,
+    ) -> Result<(), failure::Error> {
+        let id_key = if is_preview { "preview_id" } else { "id" };
+        let table = self.table_mut(env)?;
+        let namespaces = table
+            .entry("kv_namespaces")
+            .or_insert_with(|| value(Array::new()));
+
+        if let Some(namespaces) = namespaces.as_array_of_tables_mut() {
+            let existing = namespaces.iter().position(|namespace| {
+                namespace.get("binding").and_then(Item::as_str) == Some(binding)
+            });
+            match existing.and_then(|index| namespaces.get_mut(index)) {
+                Some(namespace) => {
+                    namespace.insert(id_key, value(id));
+                }
+                None => {
+                    let mut namespace = Table::new();
+                    namespace.insert("binding", value(binding));
+                    namespace.insert(id_key, value(id));
+                    namespaces.push(namespace);
+                }
+            }
+        } else if let Some(namespaces) = namespaces.as_array_mut() {
+            let existing = namespaces.iter().position(|namespace| {
+                namespace
+                    .as_inline_table()
+                    .and_then(|namespace| namespace.get("binding"))
+                    .and_then(Value::as_str)
+                    == Some(binding)
+            });
+            match existing
+                .and_then(|index| namespaces.get_mut(index))
+                .and_then(Value::as_inline_table_mut)
+            {
+                Some(namespace) => {
+                    namespace.insert(id_key, id.into());
+                    namespace.fmt();
+                }
+                None => {
+                    let mut namespace = InlineTable::new();
+                    namespace.insert("binding", binding.into());
+                    namespace.insert(id_key, id.into());
+                    push_element(namespaces, Value::InlineTable(namespace));
+                }
+            }
+        } else {
+            failure::bail!("kv_namespaces must be an array")
+        }
+
+        Ok(())
+    }
+}
+
+// Appends to an array, on a line of its own if the array already spans several lines.
+fn push_element(array: &mut Array, element: Value) {
+    let line_prefix = array
+        .iter()
+        .last()
+        .and_then(|last| last.decor().prefix())
+        .and_then(|prefix| {
+            prefix
+                .rfind('\n')
+                .map(|newline| prefix[newline..].to_string())
+        });
+
+    match line_prefix {
+        Some(prefix) => array.push_formatted(element.decorated(&prefix, "")),
+        None => array.push(element),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const CONFIG: &str = r#"# my worker
+name = "worker"
+type = "javascript"
+account_id = "account"
+workers_dev = true
+
+kv_namespaces = [
+    # the cache
+    { binding = "CACHE", id = "cache_id" },
+]
+
+[env.production]
+name = "worker-production" # keep this name
+"#;
+
+    fn edit(config: &str, edit: impl Fn(&mut ManifestEditor)) -> String {
+        let mut editor = ManifestEditor::parse(Path::new("wrangler.toml"), config).unwrap();
+        edit(&mut editor);
+        let edited = editor.document.to_string();
+        Manifest::from_str(&edited).unwrap();
+        edited
+    }
+
+    #[test]
+    fn it_adds_a_namespace_to_an_existing_array() {
+        let edited = edit(CONFIG, |editor| {
+            editor
+                .upsert_kv_namespace(None, "FOO", "foo_id", false)
+                .unwrap()
+        });
+
+        assert!(edited.contains("# the cache"));
+        assert!(edited.contains("{ binding = \"CACHE\", id = \"cache_id\" }"));
+        assert!(edited.contains("\n    { binding = \"FOO\", id = \"foo_id\" },\n]"));
+        assert!(edited.contains("name = \"worker-production\" # keep this name"));
+    }
+
+    #[test]
+    fn it_sets_the_preview_id_of_an_existing_namespace() {
+        let edited = edit(CONFIG, |editor| {
+            editor
+                .upsert_kv_namespace(None, "CACHE", "cache_preview_id", true)
+                .unwrap()
+        });
+
+        assert!(edited.contains(
+            "{ binding = \"CACHE\", id = \"cache_id\", preview_id = \"cache_preview_id\" }"
+        ));
+    }
+
+    #[test]
+    fn it_adds_namespaces_to_environments() {
+        let edited = edit(CONFIG, |editor| {
+            editor
+                .upsert_kv_namespace(Some("production"), "CACHE", "production_id", false)
+                .unwrap();
+            editor
+                .upsert_kv_namespace(Some("staging"), "CACHE", "staging_id", false)
+                .unwrap();
+        });
+
+        let manifest = Manifest::from_str(&edited).unwrap();
+        let production = manifest.get_target(Some("production"), false).unwrap();
+        assert_eq!(production.kv_namespaces[0].id, "production_id");
+        let staging = manifest.get_target(Some("staging"), false).unwrap();
+        assert_eq!(staging.kv_namespaces[0].id, "staging_id");
+        let top_level = manifest.get_target(None, false).unwrap();
+        assert_eq!(top_level.kv_namespaces[0].id, "cache_id");
+    }
+
+    #[test]
+    fn it_updates_arrays_of_tables() {
+        let config = r#"name = "worker"
+type = "javascript"
+account_id = "account"
+workers_dev = true
+
+[[kv_namespaces]]
+binding = "CACHE"
+id = "cache_id"
+"#;
+        let edited = edit(config, |editor| {
+            editor
+                .upsert_kv_namespace(None, "CACHE", "new_cache_id", false)
+                .unwrap()
+        });
+
+        assert!(edited.contains("[[kv_namespaces]]\nbinding = \"CACHE\"\nid = \"new_cache_id\""));
+    }
+}
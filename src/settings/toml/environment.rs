@@ -4,8 +4,9 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 use serde_with::rust::string_empty_as_none;
 
+use crate::settings::toml::builder::Builder;
 use crate::settings::toml::kv_namespace::ConfigKvNamespace;
-use crate::settings::toml::route::RouteConfig;
+use crate::settings::toml::route::{ConfigRoute, RouteConfig};
 use crate::settings::toml::site::Site;
 use crate::settings::toml::triggers::Triggers;
 
@@ -17,7 +18,8 @@ pub struct Environment {
     pub workers_dev: Option<bool>,
     #[serde(default, with = "string_empty_as_none")]
     pub route: Option<String>,
-    pub routes: Option<Vec<String>>,
+    pub routes: Option<Vec<ConfigRoute>>,
+    pub force_routes: Option<Vec<String>>,
     #[serde(default, with = "string_empty_as_none")]
     pub zone_id: Option<String>,
     pub webpack_config: Option<String>,
@@ -28,6 +30,7 @@ pub struct Environment {
     pub vars: Option<HashMap<String, String>>,
     pub text_blobs: Option<HashMap<String, PathBuf>>,
     pub triggers: Option<Triggers>,
+    pub build: Option<Builder>,
 }
 
 impl Environment {
@@ -47,6 +50,7 @@ impl Environment {
                 workers_dev: self.workers_dev,
                 route: self.route.clone(),
                 routes: self.routes.clone(),
+                force_routes: self.force_routes.clone(),
                 zone_id,
             })
         }
@@ -10,11 +10,12 @@ use serde::{Deserialize, Serialize};
 use serde_with::rust::string_empty_as_none;
 
 use crate::commands::{validate_worker_name, DEFAULT_CONFIG_PATH};
-use crate::deploy::{self, DeployTarget, DeploymentSet};
+use crate::deploy::{self, DeployTarget, DeploymentSet, ScheduleTarget, ZoneResolver};
+use crate::settings::toml::builder::Builder;
 use crate::settings::toml::dev::Dev;
 use crate::settings::toml::environment::Environment;
 use crate::settings::toml::kv_namespace::{ConfigKvNamespace, KvNamespace};
-use crate::settings::toml::route::RouteConfig;
+use crate::settings::toml::route::{ConfigRoute, RouteConfig};
 use crate::settings::toml::site::Site;
 use crate::settings::toml::target_type::TargetType;
 use crate::settings::toml::triggers::Triggers;
@@ -36,7 +37,8 @@ pub struct Manifest {
     pub workers_dev: Option<bool>,
     #[serde(default, with = "string_empty_as_none")]
     pub route: Option<String>,
-    pub routes: Option<Vec<String>>,
+    pub routes: Option<Vec<ConfigRoute>>,
+    pub force_routes: Option<Vec<String>>,
     #[serde(default, with = "string_empty_as_none")]
     pub zone_id: Option<String>,
     pub webpack_config: Option<String>,
@@ -51,6 +53,7 @@ pub struct Manifest {
     pub vars: Option<HashMap<String, String>>,
     pub text_blobs: Option<HashMap<String, PathBuf>>,
     pub triggers: Option<Triggers>,
+    pub build: Option<Builder>,
 }
 
 impl Manifest {
@@ -160,28 +163,64 @@ impl Manifest {
             workers_dev: self.workers_dev,
             route: self.route.clone(),
             routes: self.routes.clone(),
+            force_routes: self.force_routes.clone(),
             zone_id: self.zone_id.clone(),
         }
     }
 
+    // The route patterns declared for `env`. Environments don't inherit the top level routes.
+    pub fn route_patterns(&self, env: Option<&str>) -> Result<Vec<String>, failure::Error> {
+        let route_config = match self.get_environment(env)? {
+            Some(environment) => {
+                environment.route_config(self.account_id.clone(), self.zone_id.clone())
+            }
+            None => Some(self.route_config()),
+        };
+
+        Ok(route_config
+            .map(|route_config| route_config.patterns().map(str::to_string).collect())
+            .unwrap_or_default())
+    }
+
+    // The deployments of `env`, without any network calls: routes without a `zone_id` are only
+    // found on the zones that were already looked up. Commands that deploy routes use
+    // `get_deployments_with_zones` to look up the others.
     pub fn get_deployments(&self, env: Option<&str>) -> Result<DeploymentSet, failure::Error> {
+        let account_id = match self.get_environment(env)? {
+            Some(environment) => environment
+                .account_id
+                .clone()
+                .unwrap_or_else(|| self.account_id.clone()),
+            None => self.account_id.clone(),
+        };
+        self.get_deployments_with_zones(env, &mut ZoneResolver::cached(&account_id))
+    }
+
+    // Like `get_deployments`, resolving the zones of routes without a `zone_id` through `zones`.
+    pub fn get_deployments_with_zones(
+        &self,
+        env: Option<&str>,
+        zones: &mut ZoneResolver,
+    ) -> Result<DeploymentSet, failure::Error> {
+        let env_name = env;
         let script = self.worker_name(env);
         validate_worker_name(&script)?;
 
         let mut deployments = DeploymentSet::new();
+        let mut disabled_workers_dev = None;
 
         let env = self.get_environment(env)?;
 
         let mut add_routed_deployments =
             |route_config: &RouteConfig| -> Result<(), failure::Error> {
                 if route_config.is_zoned() {
-                    let zoned = deploy::ZonedTarget::build(&script, route_config)?;
+                    let zoned = deploy::ZonedTarget::build(&script, route_config, zones)?;
                     // This checks all of the configured routes for the wildcard ending and warns
                     // the user that their site may not work as expected without it.
                     if self.site.is_some() {
                         let no_star_routes = zoned
-                            .routes
                             .iter()
+                            .flat_map(|target| target.routes.iter())
                             .filter(|r| !r.pattern.ends_with('*'))
                             .map(|r| r.pattern.as_str())
                             .collect::<Vec<_>>();
@@ -193,7 +232,7 @@ impl Manifest {
                         }
                     }
 
-                    deployments.push(DeployTarget::Zoned(zoned));
+                    deployments.extend(zoned.into_iter().map(DeployTarget::Zoned));
                 }
 
                 if route_config.is_zoneless() {
@@ -201,6 +240,20 @@ impl Manifest {
                     deployments.push(DeployTarget::Zoneless(zoneless));
                 }
 
+                if route_config.is_workers_dev_disabled() {
+                    // taking the script off workers.dev is an account call like deploying to it
+                    let account_id = match route_config.account_id.as_ref() {
+                        Some(account_id) if !account_id.is_empty() => account_id.to_string(),
+                        _ => failure::bail!(
+                            "field `account_id` is required to take your worker off workers.dev"
+                        ),
+                    };
+                    disabled_workers_dev = Some(deploy::ZonelessTarget {
+                        account_id,
+                        script_name: script.clone(),
+                    });
+                }
+
                 Ok(())
             };
 
@@ -221,23 +274,7 @@ impl Manifest {
             add_routed_deployments(&self.route_config())
         }?;
 
-        let crons = match env {
-            Some(e) => {
-                let account_id = e.account_id.as_ref().unwrap_or(&self.account_id);
-                e.triggers
-                    .as_ref()
-                    .or_else(|| self.triggers.as_ref())
-                    .map(|t| (t.crons.as_slice(), account_id))
-            }
-            None => self
-                .triggers
-                .as_ref()
-                .map(|t| (t.crons.as_slice(), &self.account_id)),
-        };
-
-        if let Some((crons, account)) = crons {
-            let scheduled =
-                deploy::ScheduleTarget::build(account.clone(), script.clone(), crons.to_vec())?;
+        if let Some(scheduled) = self.get_schedule(env_name)? {
             deployments.push(DeployTarget::Schedule(scheduled));
         }
 
@@ -245,9 +282,54 @@ impl Manifest {
             failure::bail!("No deployments specified!")
         }
 
+        // disabling workers.dev is only done alongside an actual deployment
+        if let Some(zoneless) = disabled_workers_dev {
+            deployments.push(DeployTarget::WorkersDevDisabled(zoneless));
+        }
+
         Ok(deployments)
     }
 
+    // The crons configured for an environment, without resolving any of its routes.
+    pub fn get_schedule(
+        &self,
+        env_name: Option<&str>,
+    ) -> Result<Option<ScheduleTarget>, failure::Error> {
+        let script = self.worker_name(env_name);
+        let crons = match self.get_environment(env_name)? {
+            Some(e) => {
+                let account_id = e.account_id.as_ref().unwrap_or(&self.account_id);
+                match (&e.triggers, &self.triggers) {
+                    (Some(t), _) => Some((
+                        t.crons.as_slice(),
+                        account_id,
+                        format!("[env.{}.triggers]", env_name.unwrap_or_default()),
+                    )),
+                    (None, Some(t)) => {
+                        Some((t.crons.as_slice(), account_id, "[triggers]".to_string()))
+                    }
+                    (None, None) => None,
+                }
+            }
+            None => self.triggers.as_ref().map(|t| {
+                (
+                    t.crons.as_slice(),
+                    &self.account_id,
+                    "[triggers]".to_string(),
+                )
+            }),
+        };
+
+        match crons {
+            Some((crons, account, location)) => {
+                let scheduled = ScheduleTarget::build(account.clone(), script, crons.to_vec())
+                    .map_err(|e| failure::format_err!("{} in {}", e, location))?;
+                Ok(Some(scheduled))
+            }
+            None => Ok(None),
+        }
+    }
+
     pub fn get_account_id(&self, environment_name: Option<&str>) -> Result<String, failure::Error> {
         let environment = self.get_environment(environment_name)?;
         let mut result = self.account_id.to_string();
@@ -267,15 +349,31 @@ impl Manifest {
         }
     }
 
+    // The kv_namespaces of an environment as they are written in the configuration file,
+    // including any that are still missing an id or preview_id.
+    pub fn kv_namespace_configs(
+        &self,
+        environment_name: Option<&str>,
+    ) -> Result<Vec<ConfigKvNamespace>, failure::Error> {
+        let kv_namespaces = match self.get_environment(environment_name)? {
+            // don't inherit kv namespaces
+            Some(environment) => &environment.kv_namespaces,
+            None => &self.kv_namespaces,
+        };
+
+        Ok(kv_namespaces.clone().unwrap_or_default())
+    }
+
     pub fn get_target(
         &self,
         environment_name: Option<&str>,
         preview: bool,
     ) -> Result<Target, failure::Error> {
-        // Site projects are always webpack for now; don't let toml override this.
-        let target_type = match self.site {
-            Some(_) => TargetType::Webpack,
-            None => self.target_type.clone(),
+        // Site projects are always webpack for now, unless they configure their own build;
+        // don't let toml override this.
+        let target_type = match (&self.site, &self.build) {
+            (Some(_), None) => TargetType::Webpack,
+            _ => self.target_type.clone(),
         };
 
         /*
@@ -297,6 +395,7 @@ impl Manifest {
             site: self.site.clone(), // Inherited
             vars: self.vars.clone(), // Not inherited
             text_blobs: self.text_blobs.clone(), // Inherited
+            build: self.build.clone(), // Inherited
         };
 
         let environment = self.get_environment(environment_name)?;
@@ -317,10 +416,20 @@ impl Manifest {
                 target.site = Some(site.clone());
             }
 
+            if let Some(build) = &environment.build {
+                target.build = Some(build.clone());
+            }
+
             // don't inherit vars
             target.vars = environment.vars.clone();
         }
 
+        if target.build.is_some() && target.target_type != TargetType::JavaScript {
+            failure::bail!(
+                "The [build] section of your wrangler.toml requires type = \"javascript\""
+            )
+        }
+
         Ok(target)
     }
 
@@ -1,4 +1,6 @@
+mod builder;
 mod dev;
+mod editor;
 mod environment;
 mod kv_namespace;
 mod manifest;
@@ -8,10 +10,12 @@ mod target;
 mod target_type;
 mod triggers;
 
+pub use builder::{Builder, ModuleRule, ModuleType, UploadFormat};
+pub use editor::ManifestEditor;
 pub use environment::Environment;
 pub use kv_namespace::{ConfigKvNamespace, KvNamespace};
 pub use manifest::Manifest;
-pub use route::{Route, RouteConfig};
+pub use route::{ConfigRoute, Route, RouteConfig};
 pub use site::Site;
 pub use target::Target;
 pub use target_type::TargetType;
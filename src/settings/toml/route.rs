@@ -19,11 +19,81 @@ impl From<&WorkersRoute> for Route {
     }
 }
 
+impl Route {
+    // Whether the pattern applies to requests for `host`. A leading `*` in its hostname
+    // matches any prefix.
+    pub fn matches_host(&self, host: &str) -> bool {
+        let pattern = self
+            .pattern
+            .trim_start_matches("https://")
+            .trim_start_matches("http://");
+        let pattern_host = pattern.split('/').next().unwrap_or_default();
+        let pattern_host = pattern_host.split(':').next().unwrap_or_default();
+
+        match pattern_host.strip_prefix('*') {
+            Some(suffix) => host.ends_with(suffix),
+            None => pattern_host == host,
+        }
+    }
+}
+
+/// A route as written in the configuration file: either just its pattern, or a table that
+/// also says which zone it is on, and whether it should be taken over from another script.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum ConfigRoute {
+    Pattern(String),
+    Table {
+        pattern: String,
+        zone_id: Option<String>,
+        zone_name: Option<String>,
+        force: Option<bool>,
+    },
+}
+
+impl ConfigRoute {
+    pub fn pattern(&self) -> &str {
+        match self {
+            ConfigRoute::Pattern(pattern) => pattern,
+            ConfigRoute::Table { pattern, .. } => pattern,
+        }
+    }
+
+    pub fn zone_id(&self) -> Option<&str> {
+        match self {
+            ConfigRoute::Table {
+                zone_id: Some(zone_id),
+                ..
+            } if !zone_id.is_empty() => Some(zone_id),
+            _ => None,
+        }
+    }
+
+    pub fn zone_name(&self) -> Option<&str> {
+        match self {
+            ConfigRoute::Table {
+                zone_name: Some(zone_name),
+                ..
+            } if !zone_name.is_empty() => Some(zone_name),
+            _ => None,
+        }
+    }
+
+    pub fn force(&self) -> bool {
+        match self {
+            ConfigRoute::Table { force, .. } => force.unwrap_or_default(),
+            _ => false,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub struct RouteConfig {
     pub workers_dev: Option<bool>,
     pub route: Option<String>,
-    pub routes: Option<Vec<String>>,
+    pub routes: Option<Vec<ConfigRoute>>,
+    // patterns that may be taken over from another script on publish
+    pub force_routes: Option<Vec<String>>,
     pub zone_id: Option<String>,
     pub account_id: Option<String>,
 }
@@ -39,16 +109,24 @@ impl RouteConfig {
         }
     }
 
-    pub fn routes(&self) -> impl Iterator<Item = &String> {
-        self.route.iter().chain(self.routes.iter().flatten())
+    pub fn patterns(&self) -> impl Iterator<Item = &str> {
+        self.route
+            .iter()
+            .map(String::as_str)
+            .chain(self.routes.iter().flatten().map(ConfigRoute::pattern))
     }
 
     pub fn is_zoneless(&self) -> bool {
         self.workers_dev.unwrap_or_default()
     }
 
+    pub fn is_workers_dev_disabled(&self) -> bool {
+        self.workers_dev == Some(false)
+    }
+
+    // routes without a `zone_id` are deployed to the zone their hostname belongs to
     pub fn is_zoned(&self) -> bool {
-        self.has_routes_defined() && self.zone_id.is_some()
+        self.has_routes_defined()
     }
 
     pub fn workers_dev_false_by_itself(&self) -> bool {
@@ -1,3 +1,4 @@
+use std::collections::BTreeMap;
 use std::env;
 use std::fs;
 use std::path::PathBuf;
@@ -5,6 +6,7 @@ use std::path::PathBuf;
 use serde::{Deserialize, Serialize};
 
 use crate::commands::generate::run_generate;
+use crate::sites::Encoding;
 
 const SITE_ENTRY_POINT: &str = "workers-site";
 
@@ -16,6 +18,14 @@ pub struct Site {
     entry_point: Option<PathBuf>,
     pub include: Option<Vec<String>>,
     pub exclude: Option<Vec<String>>,
+    // encodings to store a precompressed variant of each compressible file with
+    pub precompress: Option<Vec<Encoding>>,
+    // headers to serve the files that match each glob with
+    pub headers: Option<BTreeMap<String, BTreeMap<String, String>>>,
+    // store files over the KV value size limit in chunks, instead of failing the upload
+    pub chunk_large_files: Option<bool>,
+    // how many publishes' files to keep in the namespace, instead of only the latest one's
+    pub retain_versions: Option<usize>,
 }
 
 impl Site {
@@ -62,6 +72,10 @@ impl Default for Site {
             entry_point: Some(PathBuf::from(SITE_ENTRY_POINT)),
             include: None,
             exclude: None,
+            precompress: None,
+            headers: None,
+            chunk_large_files: None,
+            retain_versions: None,
         }
     }
 }
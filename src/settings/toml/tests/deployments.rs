@@ -1,6 +1,8 @@
 use std::str::FromStr;
 
-use crate::deploy::{DeployTarget, ScheduleTarget, ZonedTarget, ZonelessTarget};
+use crate::deploy::{
+    DeployTarget, ScheduleTarget, Zone, ZoneResolver, ZonedTarget, ZonelessTarget,
+};
 use crate::settings::toml::route::Route;
 use crate::settings::toml::Manifest;
 
@@ -42,6 +44,7 @@ fn it_can_get_multiple_deployments() {
                 id: None,
             }],
             zone_id: ZONE_ID.to_owned(),
+            force_routes: Vec::new(),
         }),
         DeployTarget::Zoneless(ZonelessTarget {
             account_id: ACCOUNT_ID.to_owned(),
@@ -130,6 +133,7 @@ fn it_can_get_a_single_route_zoned_get_deployments() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -141,6 +145,53 @@ fn it_can_get_a_single_route_zoned_get_deployments_workers_dev_false() {
 
     let mut test_toml = WranglerToml::zoned_single_route(script_name, ZONE_ID, PATTERN);
     test_toml.workers_dev = Some(false);
+    test_toml.account_id = Some(ACCOUNT_ID);
+    let toml_string = toml::to_string(&test_toml).unwrap();
+    let manifest = Manifest::from_str(&toml_string).unwrap();
+
+    let expected_routes = vec![Route {
+        script: Some(script_name.to_string()),
+        pattern: PATTERN.to_string(),
+        id: None,
+    }];
+    let expected_deployments = vec![
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: ZONE_ID.to_string(),
+            routes: expected_routes,
+            force_routes: Vec::new(),
+        }),
+        DeployTarget::WorkersDevDisabled(ZonelessTarget {
+            account_id: ACCOUNT_ID.to_string(),
+            script_name: script_name.to_string(),
+        }),
+    ];
+    let environment = None;
+    let actual_deployments = manifest.get_deployments(environment).unwrap();
+
+    assert_eq!(actual_deployments, expected_deployments);
+}
+
+#[test]
+fn it_errors_on_zoned_get_deployments_workers_dev_false_missing_account_id() {
+    let script_name = "single_route_zoned_workers_dev_false_no_account_id";
+
+    let mut test_toml = WranglerToml::zoned_single_route(script_name, ZONE_ID, PATTERN);
+    test_toml.workers_dev = Some(false);
+    test_toml.account_id = None;
+    let toml_string = toml::to_string(&test_toml).unwrap();
+    let manifest = Manifest::from_str(&toml_string).unwrap();
+
+    let environment = None;
+
+    assert!(manifest.get_deployments(environment).is_err());
+}
+
+#[test]
+fn it_can_get_a_single_route_zoned_get_deployments_with_force_routes() {
+    let script_name = "single_route_zoned_force_routes";
+
+    let mut test_toml = WranglerToml::zoned_single_route(script_name, ZONE_ID, PATTERN);
+    test_toml.force_routes = Some(vec![PATTERN]);
     let toml_string = toml::to_string(&test_toml).unwrap();
     let manifest = Manifest::from_str(&toml_string).unwrap();
 
@@ -152,6 +203,7 @@ fn it_can_get_a_single_route_zoned_get_deployments_workers_dev_false() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: vec![PATTERN.to_string()],
     })];
     let environment = None;
     let actual_deployments = manifest.get_deployments(environment).unwrap();
@@ -185,6 +237,31 @@ fn it_can_get_a_scheduled_no_workers_dev_no_zoned() {
     assert_eq!(actual_deployments, expected_deployments);
 }
 
+#[test]
+fn it_gets_the_schedule_without_resolving_routes() {
+    let toml = r#"
+        name = "schedule_with_routes"
+        type = "webpack"
+        account_id = "fakeaccountid"
+        routes = ["unresolved.tld/*"]
+
+        [triggers]
+        crons = ["0 * * * *"]
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    let schedule = manifest.get_schedule(None).unwrap();
+
+    assert_eq!(
+        schedule,
+        Some(ScheduleTarget {
+            account_id: ACCOUNT_ID.to_owned(),
+            script_name: "schedule_with_routes".to_owned(),
+            crons: vec!["0 * * * *".to_owned()],
+        })
+    );
+}
+
 #[test]
 fn it_can_get_a_scheduled_in_env_no_workers_dev_no_zoned() {
     let script_name = "single_schedule";
@@ -255,6 +332,31 @@ fn it_cat_get_inherited_env_schedules() {
     assert_eq!(actual_deployments, expected_deployments);
 }
 
+#[test]
+fn it_errors_on_invalid_env_schedules() {
+    let script_name = "invalid_schedule";
+
+    let env_config = EnvConfig {
+        triggers: Some(Triggers {
+            crons: Some(vec!["0 * * * *".to_owned(), "61 * * * *".to_owned()]),
+        }),
+        ..EnvConfig::default()
+    };
+    let mut test_toml = WranglerToml::webpack(script_name);
+    test_toml.account_id = Some(ACCOUNT_ID);
+    test_toml
+        .env
+        .get_or_insert_with(Default::default)
+        .insert("b", env_config);
+
+    let toml_string = toml::to_string(&test_toml).unwrap();
+    let manifest = Manifest::from_str(&toml_string).unwrap();
+
+    let error = manifest.get_deployments(Some("b")).unwrap_err().to_string();
+    assert!(error.contains("\"61 * * * *\""));
+    assert!(error.contains("[env.b.triggers]"));
+}
+
 #[test]
 fn it_errors_on_single_route_get_deployments_empty_zone_id() {
     let script_name = "single_route_empty_zone_id";
@@ -266,7 +368,9 @@ fn it_errors_on_single_route_get_deployments_empty_zone_id() {
 
     let environment = None;
 
-    assert!(manifest.get_deployments(environment).is_err());
+    assert!(manifest
+        .get_deployments_with_zones(environment, &mut ZoneResolver::offline(Vec::new()))
+        .is_err());
 }
 
 #[test]
@@ -281,7 +385,9 @@ fn it_errors_on_single_route_get_deployments_missing_zone_id() {
 
     let environment = None;
 
-    assert!(manifest.get_deployments(environment).is_err());
+    assert!(manifest
+        .get_deployments_with_zones(environment, &mut ZoneResolver::offline(Vec::new()))
+        .is_err());
 }
 
 #[test]
@@ -334,6 +440,7 @@ fn it_can_get_a_multi_route_zoned_get_deployments() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     let environment = None;
@@ -349,6 +456,7 @@ fn it_can_get_a_multi_route_zoned_get_deployments_workers_dev_false() {
 
     let mut test_toml = WranglerToml::webpack(script_name);
     test_toml.workers_dev = Some(false);
+    test_toml.account_id = Some(ACCOUNT_ID);
     test_toml.routes = Some(patterns.to_vec());
     test_toml.zone_id = Some(ZONE_ID);
     let toml_string = toml::to_string(&test_toml).unwrap();
@@ -362,10 +470,17 @@ fn it_can_get_a_multi_route_zoned_get_deployments_workers_dev_false() {
             id: None,
         })
         .collect();
-    let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
-        zone_id: ZONE_ID.to_string(),
-        routes: expected_routes,
-    })];
+    let expected_deployments = vec![
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: ZONE_ID.to_string(),
+            routes: expected_routes,
+            force_routes: Vec::new(),
+        }),
+        DeployTarget::WorkersDevDisabled(ZonelessTarget {
+            account_id: ACCOUNT_ID.to_string(),
+            script_name: script_name.to_string(),
+        }),
+    ];
 
     let environment = None;
     let actual_deployments = manifest.get_deployments(environment).unwrap();
@@ -380,6 +495,7 @@ fn it_can_get_multi_route_with_route() {
 
     let mut test_toml = WranglerToml::webpack(script_name);
     test_toml.workers_dev = Some(false);
+    test_toml.account_id = Some(ACCOUNT_ID);
     test_toml.routes = Some(patterns.to_vec());
     test_toml.route = Some("blog.hostname.tld/*");
     test_toml.zone_id = Some(ZONE_ID);
@@ -394,10 +510,17 @@ fn it_can_get_multi_route_with_route() {
             id: None,
         })
         .collect();
-    let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
-        zone_id: ZONE_ID.to_string(),
-        routes: expected_routes,
-    })];
+    let expected_deployments = vec![
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: ZONE_ID.to_string(),
+            routes: expected_routes,
+            force_routes: Vec::new(),
+        }),
+        DeployTarget::WorkersDevDisabled(ZonelessTarget {
+            account_id: ACCOUNT_ID.to_string(),
+            script_name: script_name.to_string(),
+        }),
+    ];
 
     let environment = None;
     let actual_deployments = manifest.get_deployments(environment).unwrap();
@@ -417,7 +540,9 @@ fn it_errors_on_multi_route_get_deployments_empty_zone_id() {
 
     let environment = None;
 
-    assert!(manifest.get_deployments(environment).is_err());
+    assert!(manifest
+        .get_deployments_with_zones(environment, &mut ZoneResolver::offline(Vec::new()))
+        .is_err());
 }
 
 #[test]
@@ -434,7 +559,147 @@ fn it_errors_on_multi_route_get_deployments_missing_zone_id() {
 
     let environment = None;
 
-    assert!(manifest.get_deployments(environment).is_err());
+    assert!(manifest
+        .get_deployments_with_zones(environment, &mut ZoneResolver::offline(Vec::new()))
+        .is_err());
+}
+
+#[test]
+fn it_resolves_zones_for_routes_without_zone_id() {
+    let script_name = "multi_zone_routes";
+    let patterns = [PATTERN, "other.tld/*", "blog.hostname.tld/*"];
+
+    let mut test_toml = WranglerToml::webpack(script_name);
+    test_toml.routes = Some(patterns.to_vec());
+    test_toml.force_routes = Some(vec!["other.tld/*"]);
+    let toml_string = toml::to_string(&test_toml).unwrap();
+    let manifest = Manifest::from_str(&toml_string).unwrap();
+
+    let hostname_zone = Zone {
+        id: ZONE_ID.to_string(),
+        name: "hostname.tld".to_string(),
+    };
+    let other_zone = Zone {
+        id: "otherzoneid".to_string(),
+        name: "other.tld".to_string(),
+    };
+    let mut zones = ZoneResolver::offline(vec![
+        ("hostname.tld", hostname_zone.clone()),
+        ("blog.hostname.tld", hostname_zone),
+        ("other.tld", other_zone),
+    ]);
+
+    let route = |pattern: &str| Route {
+        script: Some(script_name.to_string()),
+        pattern: pattern.to_string(),
+        id: None,
+    };
+    let expected_deployments = vec![
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: ZONE_ID.to_string(),
+            routes: vec![route(PATTERN), route("blog.hostname.tld/*")],
+            force_routes: Vec::new(),
+        }),
+        DeployTarget::Zoned(ZonedTarget {
+            zone_id: "otherzoneid".to_string(),
+            routes: vec![route("other.tld/*")],
+            force_routes: vec!["other.tld/*".to_string()],
+        }),
+    ];
+    let actual_deployments = manifest
+        .get_deployments_with_zones(None, &mut zones)
+        .unwrap();
+
+    assert_eq!(actual_deployments, expected_deployments);
+}
+
+#[test]
+fn it_gets_deployments_for_route_tables_on_several_zones() {
+    let toml = r#"
+        name = "route_tables"
+        type = "webpack"
+        zone_id = "samplezoneid"
+        routes = [
+            "hostname.tld/*",
+            { pattern = "*.other.tld/*", zone_id = "otherzoneid" },
+            { pattern = "named.tld/*", zone_name = "named.tld" },
+        ]
+
+        [env.test]
+        routes = [{ pattern = "staging.other.tld/*", zone_id = "otherzoneid" }]
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+    let mut zones = ZoneResolver::offline(vec![(
+        "named.tld",
+        Zone {
+            id: "namedzoneid".to_string(),
+            name: "named.tld".to_string(),
+        },
+    )]);
+
+    let deployments = manifest
+        .get_deployments_with_zones(None, &mut zones)
+        .unwrap();
+    let zoned: Vec<&ZonedTarget> = deployments
+        .iter()
+        .filter_map(|deployment| match deployment {
+            DeployTarget::Zoned(zoned) => Some(zoned),
+            _ => None,
+        })
+        .collect();
+    let zone_ids: Vec<&str> = zoned.iter().map(|z| z.zone_id.as_str()).collect();
+    assert_eq!(zone_ids, vec![ZONE_ID, "otherzoneid", "namedzoneid"]);
+    assert!(zoned[1].matches_host("www.other.tld"));
+    assert!(!zoned[1].matches_host("hostname.tld"));
+
+    let deployments = manifest
+        .get_deployments_with_zones(Some(TEST_ENV_NAME), &mut zones)
+        .unwrap();
+    let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
+        zone_id: "otherzoneid".to_string(),
+        routes: vec![Route {
+            script: Some(manifest.worker_name(Some(TEST_ENV_NAME))),
+            pattern: "staging.other.tld/*".to_string(),
+            id: None,
+        }],
+        force_routes: Vec::new(),
+    })];
+    assert_eq!(deployments, expected_deployments);
+}
+
+#[test]
+fn it_gets_deployments_for_forced_route_tables() {
+    let toml = r#"
+        name = "forced_route_tables"
+        type = "webpack"
+        zone_id = "samplezoneid"
+        routes = [
+            "hostname.tld/*",
+            { pattern = "hostname.tld/api/*", force = true },
+            { pattern = "hostname.tld/blog/*", force = false },
+        ]
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    let deployments = manifest.get_deployments(None).unwrap();
+    let expected_routes = [
+        "hostname.tld/*",
+        "hostname.tld/api/*",
+        "hostname.tld/blog/*",
+    ]
+    .iter()
+    .map(|pattern| Route {
+        script: Some("forced_route_tables".to_string()),
+        pattern: pattern.to_string(),
+        id: None,
+    })
+    .collect();
+    let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
+        zone_id: ZONE_ID.to_string(),
+        routes: expected_routes,
+        force_routes: vec!["hostname.tld/api/*".to_string()],
+    })];
+    assert_eq!(deployments, expected_deployments);
 }
 
 #[test]
@@ -486,6 +751,7 @@ fn it_can_get_a_route_with_routes() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         routes: expected_routes,
         zone_id: ZONE_ID.to_owned(),
+        force_routes: Vec::new(),
     })];
 
     let environment = None;
@@ -619,7 +885,8 @@ fn when_top_level_empty_env_zoned_single_route_no_zone_id() {
     let toml_string = toml::to_string(&test_toml).unwrap();
     let manifest = Manifest::from_str(&toml_string).unwrap();
 
-    let actual_deployments = manifest.get_deployments(Some(TEST_ENV_NAME));
+    let actual_deployments = manifest
+        .get_deployments_with_zones(Some(TEST_ENV_NAME), &mut ZoneResolver::offline(Vec::new()));
 
     assert!(actual_deployments.is_err());
 }
@@ -660,6 +927,7 @@ fn when_top_level_empty_env_zoned_single_route() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -708,7 +976,8 @@ fn when_top_level_empty_zoned_multi_route_env_zone_id_missing() {
     let toml_string = toml::to_string(&test_toml).unwrap();
     let manifest = Manifest::from_str(&toml_string).unwrap();
 
-    let actual_deployments = manifest.get_deployments(Some(TEST_ENV_NAME));
+    let actual_deployments = manifest
+        .get_deployments_with_zones(Some(TEST_ENV_NAME), &mut ZoneResolver::offline(Vec::new()));
 
     assert!(actual_deployments.is_err());
 }
@@ -740,6 +1009,7 @@ fn when_top_level_empty_zoned_multi_route_env() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -840,7 +1110,8 @@ fn when_top_level_zoneless_env_zoned_single_route_zone_id_missing() {
     let toml_string = toml::to_string(&test_toml).unwrap();
     let manifest = Manifest::from_str(&toml_string).unwrap();
 
-    let actual_deployments = manifest.get_deployments(Some(TEST_ENV_NAME));
+    let actual_deployments = manifest
+        .get_deployments_with_zones(Some(TEST_ENV_NAME), &mut ZoneResolver::offline(Vec::new()));
 
     assert!(actual_deployments.is_err());
 }
@@ -869,6 +1140,7 @@ fn when_top_level_zoneless_env_zoned_single_route() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -938,6 +1210,7 @@ fn when_top_level_zoneless_env_zoned_multi_route_route_key_present() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         routes: expected_routes,
         zone_id: ZONE_ID.to_owned(),
+        force_routes: Vec::new(),
     })];
 
     let actual_deployments = manifest.get_deployments(Some(TEST_ENV_NAME)).unwrap();
@@ -958,7 +1231,8 @@ fn when_top_level_zoneless_env_zoned_multi_route_zone_id_missing() {
     let toml_string = toml::to_string(&test_toml).unwrap();
     let manifest = Manifest::from_str(&toml_string).unwrap();
 
-    let actual_deployments = manifest.get_deployments(Some(TEST_ENV_NAME));
+    let actual_deployments = manifest
+        .get_deployments_with_zones(Some(TEST_ENV_NAME), &mut ZoneResolver::offline(Vec::new()));
 
     assert!(actual_deployments.is_err());
 }
@@ -992,6 +1266,7 @@ fn when_top_level_zoneless_env_zoned_multi_route() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -1092,6 +1367,7 @@ fn when_top_level_zoned_env_zoned_single_route_zone_id_missing() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: ZONE_ID.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
@@ -1122,6 +1398,7 @@ fn when_top_level_zoned_env_zoned_single_route() {
     let expected_deployments = vec![DeployTarget::Zoned(ZonedTarget {
         zone_id: env_zone_id.to_string(),
         routes: expected_routes,
+        force_routes: Vec::new(),
     })];
 
     assert_eq!(actual_deployments, expected_deployments);
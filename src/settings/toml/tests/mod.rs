@@ -8,6 +8,7 @@ use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::fixtures::{EnvConfig, WranglerToml, TEST_ENV_NAME};
+use crate::sites::Encoding;
 
 #[test]
 fn it_builds_from_config() {
@@ -76,6 +77,93 @@ fn it_builds_from_environments_config_with_kv() {
     }
 }
 
+#[test]
+fn it_gets_kv_namespace_configs_missing_ids() {
+    let toml = r#"
+        name = "worker"
+        type = "javascript"
+        account_id = "account"
+        kv_namespaces = [{ binding = "CACHE", id = "cache_id" }]
+
+        [env.production]
+        kv_namespaces = [{ binding = "CACHE" }]
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    let top_level = manifest.kv_namespace_configs(None).unwrap();
+    assert_eq!(top_level[0].id, Some("cache_id".to_string()));
+    assert_eq!(top_level[0].preview_id, None);
+
+    let production = manifest.kv_namespace_configs(Some("production")).unwrap();
+    assert_eq!(production[0].binding, "CACHE");
+    assert_eq!(production[0].id, None);
+    assert!(manifest.get_target(Some("production"), false).is_err());
+
+    assert!(manifest.kv_namespace_configs(Some("staging")).is_err());
+}
+
+#[test]
+fn it_builds_from_modules_config() {
+    let toml_path = toml_fixture_path("modules");
+    let manifest = Manifest::new(&toml_path).unwrap();
+
+    let target = manifest.get_target(None, false).unwrap();
+    let expected_upload = UploadFormat::Modules {
+        main: PathBuf::from("index.mjs"),
+        dir: PathBuf::from("dist"),
+        rules: vec![ModuleRule {
+            globs: vec!["**/*.html".to_string()],
+            module_type: ModuleType::Text,
+            fallthrough: false,
+        }],
+    };
+    assert_eq!(target.build.unwrap().upload, expected_upload);
+
+    let target = manifest.get_target(Some("production"), false).unwrap();
+    assert_eq!(target.build.unwrap().upload, UploadFormat::default());
+}
+
+#[test]
+fn it_builds_from_custom_build_config() {
+    let toml_path = toml_fixture_path("custom_build");
+    let manifest = Manifest::new(&toml_path).unwrap();
+
+    let build = manifest.get_target(None, false).unwrap().build.unwrap();
+    assert_eq!(build.command, Some("npm run build".to_string()));
+    assert_eq!(build.cwd, PathBuf::from("worker"));
+    assert_eq!(build.watch_dir, PathBuf::from("src"));
+    assert_eq!(
+        build.artifact_path(),
+        PathBuf::from("dist").join("index.js")
+    );
+
+    // an environment's [build] replaces the top level one entirely
+    let build = manifest
+        .get_target(Some("production"), false)
+        .unwrap()
+        .build
+        .unwrap();
+    assert_eq!(build.command, Some("npm run build:production".to_string()));
+    assert_eq!(build.cwd, PathBuf::from("."));
+    assert_eq!(build.watch_dir, PathBuf::from("worker/src"));
+    assert_eq!(build.upload, UploadFormat::default());
+}
+
+#[test]
+fn it_requires_javascript_type_for_custom_builds() {
+    let toml = r#"
+        name = "custom-build-worker"
+        type = "webpack"
+        account_id = ""
+
+        [build]
+        command = "npm run build"
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    assert!(manifest.get_target(None, false).is_err());
+}
+
 #[test]
 fn parses_same_from_config_path_as_string() {
     env::remove_var("CF_ACCOUNT_ID");
@@ -124,6 +212,65 @@ fn it_uses_env_name_when_provided() {
     assert_eq!(manifest.worker_name(Some(TEST_ENV_NAME)), custom_env_name);
 }
 
+#[test]
+fn it_gets_the_route_patterns_of_an_environment() {
+    let toml = r#"
+        name = "worker"
+        type = "webpack"
+        route = "example.com/*"
+        routes = [{ pattern = "api.example.com/*", zone_name = "example.com" }]
+
+        [env.staging]
+        routes = ["staging.example.com/*"]
+
+        [env.production]
+        workers_dev = true
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    assert_eq!(
+        manifest.route_patterns(None).unwrap(),
+        vec!["example.com/*", "api.example.com/*"]
+    );
+    assert_eq!(
+        manifest.route_patterns(Some("staging")).unwrap(),
+        vec!["staging.example.com/*"]
+    );
+    assert!(manifest
+        .route_patterns(Some("production"))
+        .unwrap()
+        .is_empty());
+    assert!(manifest.route_patterns(Some("dev")).is_err());
+}
+
+#[test]
+fn it_reads_the_site_precompress_encodings() {
+    let toml = r#"
+        name = "worker"
+        type = "webpack"
+
+        [site]
+        bucket = "public"
+        precompress = ["br", "gzip"]
+    "#;
+    let manifest = Manifest::from_str(toml).unwrap();
+
+    assert_eq!(
+        manifest.site.unwrap().precompress,
+        Some(vec![Encoding::Br, Encoding::Gzip])
+    );
+
+    let toml = r#"
+        name = "worker"
+        type = "webpack"
+
+        [site]
+        bucket = "public"
+        precompress = ["deflate"]
+    "#;
+    assert!(Manifest::from_str(toml).is_err());
+}
+
 fn base_fixture_path() -> PathBuf {
     let current_dir = env::current_dir().unwrap();
 
@@ -0,0 +1,195 @@
+use std::collections::HashSet;
+use std::fs::{self, OpenOptions};
+use std::io::Write;
+use std::path::PathBuf;
+
+use crate::settings::get_project_state_dir;
+
+const CHECKPOINTS_DIR_NAME: &str = "uploads";
+
+/// The keys known to be in a Workers Sites namespace while a publish is uploading to it: the
+/// keys that were listed when the publish started, plus every batch uploaded since. It is
+/// removed once the publish finishes, so one that is left behind belongs to a publish that
+/// was interrupted.
+#[derive(Debug)]
+pub struct UploadCheckpoint {
+    path: PathBuf,
+    keys: HashSet<String>,
+}
+
+impl UploadCheckpoint {
+    pub fn new(
+        namespace_id: &str,
+        keys: HashSet<String>,
+    ) -> Result<UploadCheckpoint, failure::Error> {
+        Ok(UploadCheckpoint {
+            path: checkpoint_path(namespace_id)?,
+            keys,
+        })
+    }
+
+    // The checkpoint left behind by an interrupted publish to `namespace_id`, if there is one.
+    pub fn load(namespace_id: &str) -> Result<Option<UploadCheckpoint>, failure::Error> {
+        read(checkpoint_path(namespace_id)?)
+    }
+
+    pub fn keys(&self) -> &HashSet<String> {
+        &self.keys
+    }
+
+    // Appends a batch of uploaded keys to the saved checkpoint, so that it doesn't have to be
+    // written out again for every batch.
+    pub fn add(&mut self, keys: impl IntoIterator<Item = String>) -> Result<(), failure::Error> {
+        let batch: Vec<String> = keys.into_iter().collect();
+        let mut file = OpenOptions::new().append(true).open(&self.path)?;
+        writeln!(file, "{}", serde_json::to_string(&batch)?)?;
+        self.keys.extend(batch);
+
+        Ok(())
+    }
+
+    // Writes out every key of the checkpoint, replacing whatever was saved before.
+    pub fn save(&self) -> Result<(), failure::Error> {
+        if let Some(checkpoints_dir) = self.path.parent() {
+            fs::create_dir_all(checkpoints_dir)?;
+        }
+        // written next to the checkpoint and renamed over it, so an interrupted save can't
+        // leave half a checkpoint behind
+        let partial_path = self.path.with_extension("json.partial");
+        fs::write(
+            &partial_path,
+            format!("{}\n", serde_json::to_string(&self.keys)?),
+        )?;
+        fs::rename(partial_path, &self.path)?;
+
+        Ok(())
+    }
+
+    // Removes the checkpoint of `namespace_id` once its publish has finished.
+    pub fn remove(namespace_id: &str) -> Result<(), failure::Error> {
+        let path = checkpoint_path(namespace_id)?;
+        if path.exists() {
+            fs::remove_file(path)?;
+        }
+
+        Ok(())
+    }
+}
+
+// A checkpoint is saved as a line with the keys it started with, followed by a line for every
+// batch added since.
+fn read(path: PathBuf) -> Result<Option<UploadCheckpoint>, failure::Error> {
+    if !path.is_file() {
+        return Ok(None);
+    }
+
+    let mut keys = HashSet::new();
+    for line in fs::read_to_string(&path)?.lines() {
+        match serde_json::from_str::<Vec<String>>(line) {
+            Ok(batch) => keys.extend(batch),
+            // only the batch whose append was interrupted can be cut short, and its keys are
+            // simply uploaded again
+            Err(e) => log::info!("Skipping a line of the upload checkpoint: {}", e),
+        }
+    }
+
+    Ok(Some(UploadCheckpoint { path, keys }))
+}
+
+fn checkpoint_path(namespace_id: &str) -> Result<PathBuf, failure::Error> {
+    Ok(get_project_state_dir()?
+        .join(CHECKPOINTS_DIR_NAME)
+        .join(format!("{}.json", namespace_id)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    use crate::settings::toml::{Site, Target, TargetType};
+    use crate::sites;
+
+    fn checkpoint_at(dir: &Path, keys: &[&str]) -> UploadCheckpoint {
+        UploadCheckpoint {
+            path: dir.join("namespaceid.json"),
+            keys: keys.iter().map(|key| key.to_string()).collect(),
+        }
+    }
+
+    fn keys(keys: &[&str]) -> HashSet<String> {
+        keys.iter().map(|key| key.to_string()).collect()
+    }
+
+    #[test]
+    fn it_reads_back_every_added_batch() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = checkpoint_at(dir.path(), &["remote.a.html"]);
+        checkpoint.save().unwrap();
+        checkpoint.add(vec!["index.a.html".to_string()]).unwrap();
+        checkpoint
+            .add(vec!["app.a.css".to_string(), "logo.a.png".to_string()])
+            .unwrap();
+
+        let loaded = read(checkpoint.path.clone()).unwrap().unwrap();
+        assert_eq!(
+            loaded.keys(),
+            &keys(&["remote.a.html", "index.a.html", "app.a.css", "logo.a.png"])
+        );
+
+        fs::remove_file(&loaded.path).unwrap();
+        assert!(read(loaded.path).unwrap().is_none());
+    }
+
+    #[test]
+    fn it_skips_a_batch_that_was_cut_short() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut checkpoint = checkpoint_at(dir.path(), &["remote.a.html"]);
+        checkpoint.save().unwrap();
+        checkpoint.add(vec!["index.a.html".to_string()]).unwrap();
+        let mut file = OpenOptions::new()
+            .append(true)
+            .open(&checkpoint.path)
+            .unwrap();
+        write!(file, "[\"app.a.c").unwrap();
+
+        let loaded = read(checkpoint.path).unwrap().unwrap();
+        assert_eq!(loaded.keys(), &keys(&["remote.a.html", "index.a.html"]));
+    }
+
+    #[test]
+    fn it_skips_checkpointed_keys_on_resume() {
+        let dir = tempfile::tempdir().unwrap();
+        let bucket = dir.path().join("public");
+        fs::create_dir_all(&bucket).unwrap();
+        fs::write(bucket.join("index.html"), "<h1>hello</h1>").unwrap();
+        fs::write(bucket.join("app.css"), "h1 { color: red; }").unwrap();
+
+        let mut site = Site::default();
+        site.bucket = bucket.clone();
+        let target = Target {
+            account_id: "".to_string(),
+            kv_namespaces: Vec::new(),
+            name: "".to_string(),
+            target_type: TargetType::JavaScript,
+            webpack_config: None,
+            site: Some(site),
+            vars: None,
+            text_blobs: None,
+            build: None,
+        };
+
+        let (to_upload, _, _) = sites::diff(&target, &bucket, &HashSet::new()).unwrap();
+        assert_eq!(to_upload.len(), 2);
+
+        // the publish was interrupted after uploading its first batch
+        let mut checkpoint = checkpoint_at(dir.path(), &[]);
+        checkpoint.save().unwrap();
+        checkpoint.add(vec![to_upload[0].key.clone()]).unwrap();
+
+        let resumed = read(checkpoint.path).unwrap().unwrap();
+        let (to_upload_on_resume, _, _) = sites::diff(&target, &bucket, resumed.keys()).unwrap();
+        assert_eq!(to_upload_on_resume.len(), 1);
+        assert_eq!(to_upload_on_resume[0].key, to_upload[1].key);
+    }
+}
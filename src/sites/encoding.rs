@@ -0,0 +1,129 @@
+use std::fmt;
+use std::io::Write;
+use std::path::Path;
+
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use serde::{Deserialize, Serialize};
+
+// the brotli window size, 4MiB, the largest a browser is guaranteed to decode
+const BROTLI_WINDOW_BITS: u32 = 22;
+const BROTLI_QUALITY: u32 = 11;
+const BROTLI_BUFFER_SIZE: usize = 4096;
+
+// files with these extensions are text, or binary formats that aren't compressed already
+const COMPRESSIBLE_EXTENSIONS: &[&str] = &[
+    "css",
+    "csv",
+    "eot",
+    "htm",
+    "html",
+    "ico",
+    "js",
+    "json",
+    "map",
+    "md",
+    "mjs",
+    "otf",
+    "rss",
+    "svg",
+    "ttf",
+    "txt",
+    "wasm",
+    "webmanifest",
+    "xml",
+];
+
+/// A content encoding that Workers Sites files can be precompressed with, as configured in
+/// `[site] precompress`.
+#[derive(Clone, Copy, Debug, Deserialize, Eq, Hash, Ord, PartialEq, PartialOrd, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Encoding {
+    Br,
+    Gzip,
+}
+
+impl Encoding {
+    // The extension added to the key of a file that is compressed with this encoding.
+    pub fn extension(self) -> &'static str {
+        match self {
+            Encoding::Br => "br",
+            Encoding::Gzip => "gz",
+        }
+    }
+
+    pub fn compress(self, value: &[u8]) -> Result<Vec<u8>, failure::Error> {
+        match self {
+            Encoding::Br => {
+                let mut compressed = Vec::new();
+                {
+                    let mut writer = brotli::CompressorWriter::new(
+                        &mut compressed,
+                        BROTLI_BUFFER_SIZE,
+                        BROTLI_QUALITY,
+                        BROTLI_WINDOW_BITS,
+                    );
+                    writer.write_all(value)?;
+                }
+                Ok(compressed)
+            }
+            Encoding::Gzip => {
+                let mut writer = GzEncoder::new(Vec::new(), Compression::best());
+                writer.write_all(value)?;
+                Ok(writer.finish()?)
+            }
+        }
+    }
+}
+
+impl fmt::Display for Encoding {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Encoding::Br => write!(f, "br"),
+            Encoding::Gzip => write!(f, "gzip"),
+        }
+    }
+}
+
+// Whether precompressing the file at `path` is worth it, judging by its extension.
+pub fn is_compressible(path: &Path) -> bool {
+    match path.extension().and_then(|extension| extension.to_str()) {
+        Some(extension) => COMPRESSIBLE_EXTENSIONS.contains(&extension.to_lowercase().as_str()),
+        None => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::read::GzDecoder;
+    use std::io::Read;
+
+    #[test]
+    fn it_compresses_values() {
+        let value = "body { color: red; }\n".repeat(100);
+
+        let gzipped = Encoding::Gzip.compress(value.as_bytes()).unwrap();
+        let mut decoded = String::new();
+        GzDecoder::new(gzipped.as_slice())
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, value);
+
+        let brotli = Encoding::Br.compress(value.as_bytes()).unwrap();
+        let mut decoded = String::new();
+        brotli::Decompressor::new(brotli.as_slice(), BROTLI_BUFFER_SIZE)
+            .read_to_string(&mut decoded)
+            .unwrap();
+        assert_eq!(decoded, value);
+        assert!(brotli.len() < value.len());
+    }
+
+    #[test]
+    fn it_only_compresses_compressible_files() {
+        assert!(is_compressible(Path::new("css/app.css")));
+        assert!(is_compressible(Path::new("INDEX.HTML")));
+        assert!(!is_compressible(Path::new("img/logo.png")));
+        assert!(!is_compressible(Path::new("LICENSE")));
+    }
+}
@@ -0,0 +1,335 @@
+use std::collections::HashSet;
+
+use chrono::Utc;
+use cloudflare::framework::response::ApiFailure;
+use indicatif::{ProgressBar, ProgressStyle};
+use serde::{Deserialize, Serialize};
+
+use super::{remote_keys, AssetManifest};
+use crate::commands::kv;
+use crate::http;
+use crate::kv::bulk::{self, KeyValuePair};
+use crate::kv::key::KeyList;
+use crate::settings::global_user::GlobalUser;
+use crate::settings::toml::Target;
+use crate::terminal::message::{Message, StdErr};
+
+// Generations are stored in the Workers Sites namespace itself rather than in the project
+// directory, so that every machine publishing the site collects garbage from the same history.
+const GENERATION_KEY_PREFIX: &str = "__wrangler_generations/";
+
+/// The asset manifest of a publish, kept so that the keys it uses aren't deleted until it is
+/// older than the last `[site] retain_versions` generations.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct Generation {
+    pub id: String,
+    pub created_on: String,
+    pub asset_manifest: AssetManifest,
+}
+
+/// What collecting garbage deletes: the keys that none of the retained generations use, and
+/// the ids of the generations that are no longer retained.
+#[derive(Debug, Default)]
+pub struct Garbage {
+    pub keys: Vec<String>,
+    pub generations: Vec<String>,
+}
+
+impl Generation {
+    pub fn new(asset_manifest: AssetManifest) -> Generation {
+        let now = Utc::now();
+        Generation {
+            id: now.format("%Y%m%d%H%M%S%3f").to_string(),
+            created_on: now.to_rfc3339(),
+            asset_manifest,
+        }
+    }
+
+    // Stores the generation in the Workers Sites namespace `namespace_id`.
+    pub fn record(
+        &self,
+        target: &Target,
+        user: &GlobalUser,
+        namespace_id: &str,
+    ) -> Result<(), failure::Error> {
+        let pair = KeyValuePair {
+            key: generation_key(&self.id),
+            value: serde_json::to_string(self)?,
+            expiration: None,
+            expiration_ttl: None,
+            metadata: None,
+            base64: None,
+        };
+        bulk::put(target, user, namespace_id, vec![pair], &None)
+    }
+}
+
+// Records `asset_manifest` as the newest generation of the site of `target` when it retains
+// versions, so that the files it uses aren't collected, and returns whether it was recorded.
+// A deploy that went through isn't failed by its generation, so this only warns.
+pub fn record_generation(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+    asset_manifest: &AssetManifest,
+) -> bool {
+    let retains_versions = target
+        .site
+        .as_ref()
+        .map_or(false, |site| site.retain_versions.is_some());
+    if !retains_versions {
+        return false;
+    }
+
+    let generation = Generation::new(asset_manifest.clone());
+    match generation.record(target, user, namespace_id) {
+        Ok(()) => {
+            StdErr::info(&format!("Recorded as generation {}", generation.id));
+            true
+        }
+        Err(e) => {
+            StdErr::warn(&format!(
+                "Could not record this generation of your site, so the files it uses aren't kept: {}",
+                e
+            ));
+            false
+        }
+    }
+}
+
+pub fn validate_retain_versions(retain_versions: usize) -> Result<(), failure::Error> {
+    if retain_versions == 0 {
+        failure::bail!("`retain_versions` in [site] must be at least 1")
+    }
+
+    Ok(())
+}
+
+pub fn is_generation_key(key: &str) -> bool {
+    key.starts_with(GENERATION_KEY_PREFIX)
+}
+
+// Finds what collecting garbage would delete, keeping the last `retain_versions` generations.
+// `pending` is the asset manifest of a publish that hasn't been recorded yet, which counts as
+// the newest generation.
+pub fn find_garbage(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+    retain_versions: usize,
+    pending: Option<&AssetManifest>,
+) -> Result<Garbage, failure::Error> {
+    validate_retain_versions(retain_versions)?;
+
+    let retain_recorded = if pending.is_some() {
+        retain_versions - 1
+    } else {
+        retain_versions
+    };
+    let (expired, retained) =
+        split_generations(generation_ids(target, user, namespace_id)?, retain_recorded);
+    if retained.is_empty() && pending.is_none() {
+        failure::bail!("No generations of this Workers Site have been recorded yet, they are recorded every time you run `wrangler publish` with `retain_versions` set in [site]")
+    }
+
+    let mut asset_manifests = Vec::new();
+    for id in &retained {
+        asset_manifests.push(get_generation(target, user, namespace_id, id)?.asset_manifest);
+    }
+    asset_manifests.extend(pending.cloned());
+
+    let remote_keys = remote_keys(target, user, namespace_id)?;
+    Ok(Garbage {
+        keys: unreferenced_keys(&remote_keys, &asset_manifests),
+        generations: expired,
+    })
+}
+
+// Deletes the keys that none of the last `retain_versions` generations use, along with the
+// generations before those.
+pub fn collect_garbage(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+    retain_versions: usize,
+) -> Result<Garbage, failure::Error> {
+    let garbage = find_garbage(target, user, namespace_id, retain_versions, None)?;
+
+    // the keys go first, so that an interrupted collection still has the generations it needs
+    // to run again
+    if !garbage.keys.is_empty() {
+        StdErr::info(&format!(
+            "Deleting {} file(s) that the last {} generation(s) don't use...",
+            garbage.keys.len(),
+            retain_versions
+        ));
+
+        let delete_progress_bar = if garbage.keys.len() > bulk::BATCH_KEY_MAX {
+            let delete_progress_bar = ProgressBar::new(garbage.keys.len() as u64);
+            delete_progress_bar
+                .set_style(ProgressStyle::default_bar().template("{wide_bar} {pos}/{len}\n{msg}"));
+            Some(delete_progress_bar)
+        } else {
+            None
+        };
+
+        bulk::delete(
+            target,
+            user,
+            namespace_id,
+            garbage.keys.clone(),
+            &delete_progress_bar,
+        )?;
+
+        if let Some(pb) = delete_progress_bar {
+            pb.finish_with_message("Done deleting");
+        }
+    }
+
+    if !garbage.generations.is_empty() {
+        let generation_keys = garbage
+            .generations
+            .iter()
+            .map(|id| generation_key(id))
+            .collect();
+        bulk::delete(target, user, namespace_id, generation_keys, &None)?;
+    }
+
+    Ok(garbage)
+}
+
+fn generation_key(id: &str) -> String {
+    format!("{}{}", GENERATION_KEY_PREFIX, id)
+}
+
+// The ids of every generation recorded in the namespace, oldest first.
+fn generation_ids(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+) -> Result<Vec<String>, failure::Error> {
+    let client = http::cf_v4_client(user)?;
+    let mut ids = Vec::new();
+    for key in KeyList::new(target, client, namespace_id, Some(GENERATION_KEY_PREFIX))? {
+        match key {
+            Ok(key) => ids.push(key.name[GENERATION_KEY_PREFIX.len()..].to_string()),
+            Err(e) => failure::bail!(kv::format_error(e)),
+        }
+    }
+    // ids are timestamps, so they sort chronologically
+    ids.sort();
+
+    Ok(ids)
+}
+
+fn get_generation(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+    id: &str,
+) -> Result<Generation, failure::Error> {
+    let api_endpoint = format!(
+        "https://api.cloudflare.com/client/v4/accounts/{}/storage/kv/namespaces/{}/values/{}",
+        target.account_id,
+        namespace_id,
+        kv::url_encode_key(&generation_key(id))
+    );
+
+    let client = http::legacy_auth_client(user);
+    let res = client.get(&api_endpoint).send()?;
+
+    let response_status = res.status();
+    if !response_status.is_success() {
+        let errors = res.json().unwrap_or_default();
+        failure::bail!(
+            "Could not read generation {}: {}",
+            id,
+            kv::format_error(ApiFailure::Error(response_status, errors))
+        )
+    }
+
+    Ok(serde_json::from_str(&res.text()?)?)
+}
+
+// Splits the ids of the recorded generations, oldest first, into the ones that have expired and
+// the last `retain` ones.
+fn split_generations(mut ids: Vec<String>, retain: usize) -> (Vec<String>, Vec<String>) {
+    let retained = ids.split_off(ids.len().saturating_sub(retain));
+    (ids, retained)
+}
+
+fn unreferenced_keys(
+    remote_keys: &HashSet<String>,
+    asset_manifests: &[AssetManifest],
+) -> Vec<String> {
+    let referenced: HashSet<&String> = asset_manifests
+        .iter()
+        .flat_map(|asset_manifest| asset_manifest.kv_keys())
+        .collect();
+
+    remote_keys
+        .iter()
+        .filter(|key| !referenced.contains(key))
+        .map(|key| key.to_owned())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::sites::Asset;
+
+    fn asset_manifest(assets: &[(&str, &str)]) -> AssetManifest {
+        let mut asset_manifest = AssetManifest::new();
+        for (path, key) in assets {
+            asset_manifest.insert(path.to_string(), Asset::new(key.to_string()));
+        }
+        asset_manifest
+    }
+
+    #[test]
+    fn it_retains_the_latest_generations() {
+        let ids = vec![
+            "20210101000000000",
+            "20210102000000000",
+            "20210103000000000",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect::<Vec<_>>();
+
+        let (expired, retained) = split_generations(ids.clone(), 2);
+        assert_eq!(expired, vec!["20210101000000000"]);
+        assert_eq!(retained, vec!["20210102000000000", "20210103000000000"]);
+
+        let (expired, retained) = split_generations(ids.clone(), 5);
+        assert!(expired.is_empty());
+        assert_eq!(retained, ids);
+
+        let (expired, retained) = split_generations(ids.clone(), 0);
+        assert_eq!(expired, ids);
+        assert!(retained.is_empty());
+    }
+
+    #[test]
+    fn it_only_collects_keys_no_retained_generation_uses() {
+        let remote_keys: HashSet<String> = vec![
+            "index.a.html",
+            "index.b.html",
+            "index.c.html",
+            "app.a.css",
+            "logo.a.png",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let asset_manifests = vec![
+            asset_manifest(&[("index.html", "index.b.html"), ("app.css", "app.a.css")]),
+            asset_manifest(&[("index.html", "index.c.html"), ("app.css", "app.a.css")]),
+        ];
+
+        let mut garbage = unreferenced_keys(&remote_keys, &asset_manifests);
+        garbage.sort();
+        assert_eq!(garbage, vec!["index.a.html", "logo.a.png"]);
+    }
+}
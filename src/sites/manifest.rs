@@ -1,3 +1,204 @@
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 
-pub type AssetManifest = HashMap<String, String>;
+use serde::{Deserialize, Serialize};
+
+use super::{AssetMetadata, Encoding, SiteRules};
+
+/// Maps the url-safe path of every file of a Workers Site to the keys it is stored under.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+#[serde(transparent)]
+pub struct AssetManifest {
+    assets: HashMap<String, Asset>,
+    // the compiled `_headers` and `_redirects` of the site, which are uploaded with the script
+    // rather than to KV
+    #[serde(skip)]
+    rules: Option<SiteRules>,
+}
+
+/// The key of a file, the keys of its precompressed variants by encoding, and the metadata
+/// stored with the file. A file too large for one KV value is stored as the ordered `chunks`
+/// instead, and nothing is stored under its key.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(from = "AssetEntry")]
+pub struct Asset {
+    pub key: String,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub encodings: BTreeMap<Encoding, String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub chunks: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<AssetMetadata>,
+}
+
+// Deployments recorded before files could be precompressed map each path straight to its key.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum AssetEntry {
+    Key(String),
+    Asset {
+        key: String,
+        #[serde(default)]
+        encodings: BTreeMap<Encoding, String>,
+        #[serde(default)]
+        chunks: Vec<String>,
+        #[serde(default)]
+        metadata: Option<AssetMetadata>,
+    },
+}
+
+impl From<AssetEntry> for Asset {
+    fn from(entry: AssetEntry) -> Asset {
+        match entry {
+            AssetEntry::Key(key) => Asset::new(key),
+            AssetEntry::Asset {
+                key,
+                encodings,
+                chunks,
+                metadata,
+            } => Asset {
+                key,
+                encodings,
+                chunks,
+                metadata,
+            },
+        }
+    }
+}
+
+impl Asset {
+    pub fn new(key: String) -> Asset {
+        Asset {
+            key,
+            encodings: BTreeMap::new(),
+            chunks: Vec::new(),
+            metadata: None,
+        }
+    }
+}
+
+impl AssetManifest {
+    pub fn new() -> AssetManifest {
+        AssetManifest::default()
+    }
+
+    pub fn insert(&mut self, path: String, asset: Asset) {
+        self.assets.insert(path, asset);
+    }
+
+    pub fn get(&self, path: &str) -> Option<&Asset> {
+        self.assets.get(path)
+    }
+
+    pub fn len(&self) -> usize {
+        self.assets.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.assets.is_empty()
+    }
+
+    pub fn rules(&self) -> Option<&SiteRules> {
+        self.rules.as_ref()
+    }
+
+    pub fn set_rules(&mut self, rules: Option<SiteRules>) {
+        self.rules = rules;
+    }
+
+    // Every key stored for the manifest, including the keys of precompressed variants and chunks.
+    pub fn kv_keys(&self) -> impl Iterator<Item = &String> {
+        self.assets.values().flat_map(|asset| {
+            // only the chunks of a chunked file are stored
+            let key = if asset.chunks.is_empty() {
+                Some(&asset.key)
+            } else {
+                None
+            };
+            key.into_iter()
+                .chain(&asset.chunks)
+                .chain(asset.encodings.values())
+        })
+    }
+
+    // Whether every file is stored under its key alone, without precompressed variants or
+    // chunks, so that the flat `original_keys` are all a worker needs. The metadata of each key
+    // is stored in KV with it, so it doesn't count.
+    pub fn is_flat(&self) -> bool {
+        self.assets
+            .values()
+            .all(|asset| asset.encodings.is_empty() && asset.chunks.is_empty())
+    }
+
+    // The flat path to key map of the original files, which is what `__STATIC_CONTENT_MANIFEST`
+    // has always been and what existing Workers Sites templates read. Chunked files aren't
+    // stored under their key, so they are left out.
+    pub fn original_keys(&self) -> HashMap<&str, &str> {
+        self.assets
+            .iter()
+            .filter(|(_, asset)| asset.chunks.is_empty())
+            .map(|(path, asset)| (path.as_str(), asset.key.as_str()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_reads_flat_asset_manifests() {
+        let asset_manifest: AssetManifest = serde_json::from_str(
+            r#"{"index.html": "index.1a2b3c4d5e.html", "app.css": {"key": "app.0f9e8d7c6b.css", "encodings": {"br": "app.0f9e8d7c6b.css.br"}}}"#,
+        )
+        .unwrap();
+
+        assert_eq!(
+            asset_manifest.get("index.html"),
+            Some(&Asset::new("index.1a2b3c4d5e.html".to_string()))
+        );
+        assert_eq!(
+            asset_manifest.get("app.css").unwrap().encodings[&Encoding::Br],
+            "app.0f9e8d7c6b.css.br"
+        );
+        assert_eq!(asset_manifest.kv_keys().count(), 3);
+    }
+
+    #[test]
+    fn it_lists_the_chunks_of_chunked_files_instead_of_their_key() {
+        let mut asset = Asset::new("video.1a2b3c4d5e.mp4".to_string());
+        asset.chunks = vec![
+            "video.1a2b3c4d5e.mp4.chunk0".to_string(),
+            "video.1a2b3c4d5e.mp4.chunk1".to_string(),
+        ];
+        let mut asset_manifest = AssetManifest::new();
+        asset_manifest.insert("video.mp4".to_string(), asset);
+
+        let mut kv_keys: Vec<&String> = asset_manifest.kv_keys().collect();
+        kv_keys.sort();
+        assert_eq!(
+            kv_keys,
+            vec!["video.1a2b3c4d5e.mp4.chunk0", "video.1a2b3c4d5e.mp4.chunk1"]
+        );
+        assert!(!asset_manifest.is_flat());
+        assert!(asset_manifest.original_keys().is_empty());
+    }
+
+    #[test]
+    fn it_keeps_the_original_keys_by_path() {
+        let mut asset = Asset::new("app.0f9e8d7c6b.css".to_string());
+        asset
+            .encodings
+            .insert(Encoding::Gzip, "app.0f9e8d7c6b.css.gz".to_string());
+        let mut asset_manifest = AssetManifest::new();
+        asset_manifest.insert("app.css".to_string(), asset);
+
+        assert_eq!(
+            serde_json::to_string(&asset_manifest.original_keys()).unwrap(),
+            r#"{"app.css":"app.0f9e8d7c6b.css"}"#
+        );
+        assert_eq!(
+            serde_json::to_string(&asset_manifest).unwrap(),
+            r#"{"app.css":{"key":"app.0f9e8d7c6b.css","encodings":{"gzip":"app.0f9e8d7c6b.css.gz"}}}"#
+        );
+    }
+}
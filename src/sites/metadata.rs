@@ -0,0 +1,245 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io::Read;
+use std::path::Path;
+
+use http::header::{HeaderName, HeaderValue};
+use ignore::overrides::{Override, OverrideBuilder};
+use serde::{Deserialize, Serialize};
+
+use super::Encoding;
+use crate::settings::toml::Site;
+
+// the most metadata Workers KV stores with a value
+pub const METADATA_MAX_SIZE: usize = 1024;
+// how much of a file is read to sniff its content type
+const SNIFF_LEN: u64 = 512;
+
+// checked in order, the first match wins
+const MAGIC_NUMBERS: &[(&[u8], &str)] = &[
+    (b"\x89PNG\r\n\x1a\n", "image/png"),
+    (b"\xff\xd8\xff", "image/jpeg"),
+    (b"GIF87a", "image/gif"),
+    (b"GIF89a", "image/gif"),
+    (b"%PDF-", "application/pdf"),
+    (b"\0asm", "application/wasm"),
+    (b"wOFF", "font/woff"),
+    (b"wOF2", "font/woff2"),
+    (b"\x1f\x8b", "application/gzip"),
+    (b"PK\x03\x04", "application/zip"),
+];
+const MARKUP_PREFIXES: &[(&str, &str)] = &[
+    ("<!doctype html", "text/html"),
+    ("<html", "text/html"),
+    ("<svg", "image/svg+xml"),
+    ("<?xml", "application/xml"),
+];
+
+/// What a worker needs to serve a file of a Workers Site, stored as the KV metadata of its keys.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AssetMetadata {
+    pub content_type: String,
+    pub etag: String,
+    // the headers of every `[site.headers]` glob that matches the file
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub headers: BTreeMap<String, String>,
+    // only set for precompressed variants
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_encoding: Option<Encoding>,
+}
+
+impl AssetMetadata {
+    // The metadata of the variant of a file compressed with `encoding`.
+    pub fn encoded(&self, encoding: Encoding) -> AssetMetadata {
+        AssetMetadata {
+            etag: format!("{}-{}\"", self.etag.trim_end_matches('"'), encoding),
+            content_encoding: Some(encoding),
+            ..self.clone()
+        }
+    }
+
+    // The metadata as KV stores it, making sure it fits.
+    pub fn to_value(&self, path: &Path) -> Result<serde_json::Value, failure::Error> {
+        let value = serde_json::to_value(self)?;
+        let len = value.to_string().len();
+        if len > METADATA_MAX_SIZE {
+            failure::bail!(
+                "The metadata of `{}` is {} bytes, more than the {} bytes Workers KV allows. Set fewer [site.headers] for it.",
+                path.display(),
+                len,
+                METADATA_MAX_SIZE
+            )
+        }
+
+        Ok(value)
+    }
+}
+
+/// The `[site.headers]` of a Workers Site, compiled into matchers for its bucket.
+pub struct HeaderRules {
+    // shortest glob first, so that the headers of longer, more specific globs win
+    rules: Vec<(Override, BTreeMap<String, String>)>,
+}
+
+impl HeaderRules {
+    pub fn new(site: Option<&Site>, directory: &Path) -> Result<HeaderRules, failure::Error> {
+        let headers = match site.and_then(|site| site.headers.as_ref()) {
+            Some(headers) => headers,
+            None => return Ok(HeaderRules { rules: Vec::new() }),
+        };
+
+        let mut globs: Vec<&String> = headers.keys().collect();
+        globs.sort_by_key(|glob| glob.len());
+
+        let mut rules = Vec::new();
+        for glob in globs {
+            let glob_headers = &headers[glob];
+            for (name, value) in glob_headers {
+                if HeaderName::from_bytes(name.as_bytes()).is_err() {
+                    failure::bail!(
+                        "[site.headers] \"{}\": \"{}\" is not a valid header name",
+                        glob,
+                        name
+                    )
+                }
+                if HeaderValue::from_str(value).is_err() {
+                    failure::bail!(
+                        "[site.headers] \"{}\": the value of \"{}\" is not a valid header value",
+                        glob,
+                        name
+                    )
+                }
+            }
+
+            let mut matcher = OverrideBuilder::new(directory);
+            matcher.add(glob)?;
+            rules.push((matcher.build()?, glob_headers.clone()));
+        }
+
+        Ok(HeaderRules { rules })
+    }
+
+    // The headers of every glob that matches `path`.
+    pub fn headers(&self, path: &Path) -> BTreeMap<String, String> {
+        let mut headers = BTreeMap::new();
+        for (matcher, glob_headers) in &self.rules {
+            if matcher.matched(path, false).is_whitelist() {
+                for (name, value) in glob_headers {
+                    headers.insert(name.clone(), value.clone());
+                }
+            }
+        }
+
+        headers
+    }
+}
+
+// The content type of the file at `path`, from its extension, or from its first bytes when the
+// extension doesn't tell.
+pub fn content_type(path: &Path) -> Result<String, failure::Error> {
+    let mime = match mime_guess::from_path(path).first() {
+        Some(mime) => mime.essence_str().to_string(),
+        None => {
+            let mut start = Vec::new();
+            fs::File::open(path)?
+                .take(SNIFF_LEN)
+                .read_to_end(&mut start)?;
+            sniff(&start).to_string()
+        }
+    };
+
+    if mime.starts_with("text/")
+        || mime == "application/javascript"
+        || mime == "application/json"
+        || mime == "application/xml"
+        || mime == "image/svg+xml"
+    {
+        Ok(format!("{}; charset=utf-8", mime))
+    } else {
+        Ok(mime)
+    }
+}
+
+fn sniff(start: &[u8]) -> &'static str {
+    for (magic_number, mime) in MAGIC_NUMBERS {
+        if start.starts_with(magic_number) {
+            return mime;
+        }
+    }
+    if start.len() >= 12 && &start[..4] == b"RIFF" && &start[8..12] == b"WEBP" {
+        return "image/webp";
+    }
+
+    match std::str::from_utf8(start) {
+        // a multi-byte character may have been cut off at the end
+        Err(e) if e.error_len().is_some() => "application/octet-stream",
+        _ if start.contains(&0) => "application/octet-stream",
+        _ => {
+            let text = String::from_utf8_lossy(start).trim_start().to_lowercase();
+            MARKUP_PREFIXES
+                .iter()
+                .find(|(prefix, _)| text.starts_with(prefix))
+                .map(|(_, mime)| *mime)
+                .unwrap_or("text/plain")
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_sniffs_content_types() {
+        assert_eq!(sniff(b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR"), "image/png");
+        assert_eq!(sniff(b"RIFF\0\0\0\0WEBPVP8 "), "image/webp");
+        assert_eq!(sniff(b"\n  <!DOCTYPE html>\n<html>"), "text/html");
+        assert_eq!(sniff(b"Just some notes"), "text/plain");
+        // cut off in the middle of a character
+        assert_eq!(sniff(&"caf\u{e9}".as_bytes()[..4]), "text/plain");
+        assert_eq!(sniff(b"\xfe\xff\0\x01"), "application/octet-stream");
+    }
+
+    #[test]
+    fn it_prefers_the_headers_of_longer_globs() {
+        let mut headers = BTreeMap::new();
+        let mut everything = BTreeMap::new();
+        everything.insert("Cache-Control".to_string(), "no-cache".to_string());
+        everything.insert("X-Frame-Options".to_string(), "DENY".to_string());
+        headers.insert("*".to_string(), everything);
+        let mut assets = BTreeMap::new();
+        assets.insert(
+            "Cache-Control".to_string(),
+            "public, max-age=31536000, immutable".to_string(),
+        );
+        headers.insert("assets/**".to_string(), assets);
+        let mut site = Site::new("public");
+        site.headers = Some(headers);
+
+        let rules = HeaderRules::new(Some(&site), Path::new("public")).unwrap();
+
+        let asset_headers = rules.headers(Path::new("public/assets/app.css"));
+        assert_eq!(
+            asset_headers["Cache-Control"],
+            "public, max-age=31536000, immutable"
+        );
+        assert_eq!(asset_headers["X-Frame-Options"], "DENY");
+        assert_eq!(
+            rules.headers(Path::new("public/index.html"))["Cache-Control"],
+            "no-cache"
+        );
+    }
+
+    #[test]
+    fn it_rejects_invalid_headers() {
+        let mut invalid = BTreeMap::new();
+        invalid.insert("Cache Control".to_string(), "no-cache".to_string());
+        let mut headers = BTreeMap::new();
+        headers.insert("*".to_string(), invalid);
+        let mut site = Site::new("public");
+        site.headers = Some(headers);
+
+        assert!(HeaderRules::new(Some(&site), Path::new("public")).is_err());
+    }
+}
@@ -1,14 +1,27 @@
 extern crate base64;
 
+mod checkpoint;
+mod encoding;
+mod generations;
 mod manifest;
+mod metadata;
+mod rules;
 mod sync;
 
-pub use manifest::AssetManifest;
-pub use sync::sync;
+pub use checkpoint::UploadCheckpoint;
+pub use encoding::Encoding;
+pub use generations::{
+    collect_garbage, find_garbage, record_generation, validate_retain_versions, Garbage, Generation,
+};
+pub use manifest::{Asset, AssetManifest};
+pub use metadata::AssetMetadata;
+pub use rules::SiteRules;
+pub use sync::{diff, remote_keys, sync};
 
 use std::ffi::OsString;
 use std::fs;
 use std::hash::Hasher;
+use std::io::{ErrorKind, Read};
 use std::path::Path;
 
 use failure::format_err;
@@ -17,15 +30,22 @@ use ignore::{Walk, WalkBuilder};
 use indicatif::{ProgressBar, ProgressStyle};
 use twox_hash::XxHash64;
 
-use cloudflare::endpoints::workerskv::write_bulk::KeyValuePair;
-
-use crate::kv::namespace::{upsert, UpsertedNamespace};
+use crate::http;
+use crate::kv::bulk::KeyFile;
+use crate::kv::namespace::{list, upsert, UpsertedNamespace};
 use crate::settings::global_user::GlobalUser;
 use crate::settings::toml::{KvNamespace, Target};
 use crate::terminal::message::{Message, StdErr};
+use metadata::HeaderRules;
+
 pub const KEY_MAX_SIZE: usize = 512;
 // Oddly enough, metadata.len() returns a u64, not usize.
 pub const VALUE_MAX_SIZE: u64 = 25 * 1024 * 1024;
+// files are hashed this many bytes at a time, a multiple of 3 so that each chunk can be base64
+// encoded on its own
+const DIGEST_CHUNK_SIZE: usize = 3 * 64 * 1024;
+// with `[site] chunk_large_files`, files over VALUE_MAX_SIZE are stored in values of this size
+const CHUNK_SIZE: u64 = 10 * 1024 * 1024;
 
 // Updates given Target with kv_namespace binding for a static site assets KV namespace.
 pub fn add_namespace(
@@ -33,13 +53,9 @@ pub fn add_namespace(
     target: &mut Target,
     preview: bool,
 ) -> Result<KvNamespace, failure::Error> {
-    let title = if preview {
-        format!("__{}-{}", target.name, "workers_sites_assets_preview")
-    } else {
-        format!("__{}-{}", target.name, "workers_sites_assets")
-    };
+    let title = namespace_title(target, preview);
 
-    let site_namespace = match upsert(target, &user, title)? {
+    let site_namespace = match upsert(&target.account_id, &user, title)? {
         UpsertedNamespace::Created(namespace) => {
             let msg = format!("Created namespace for Workers Site \"{}\"", namespace.title);
             StdErr::working(&msg);
@@ -64,16 +80,61 @@ pub fn add_namespace(
     Ok(site_namespace)
 }
 
-// Returns the hashed key and value pair for all files in a directory.
-pub fn directory_keys_values(
+// Looks up the static site assets KV namespace without creating it, so that callers
+// which must not make any changes (e.g. `wrangler publish --dry-run`) can inspect it.
+pub fn find_namespace(
+    user: &GlobalUser,
+    target: &Target,
+    preview: bool,
+) -> Result<Option<KvNamespace>, failure::Error> {
+    let title = namespace_title(target, preview);
+    let client = http::cf_v4_client(user)?;
+
+    let site_namespace = list(&client, &target.account_id)?
+        .into_iter()
+        .find(|namespace| namespace.title == title)
+        .map(|namespace| KvNamespace {
+            binding: "__STATIC_CONTENT".to_string(),
+            id: namespace.id,
+        });
+
+    Ok(site_namespace)
+}
+
+fn namespace_title(target: &Target, preview: bool) -> String {
+    if preview {
+        format!("__{}-{}", target.name, "workers_sites_assets_preview")
+    } else {
+        format!("__{}-{}", target.name, "workers_sites_assets")
+    }
+}
+
+// Returns the hashed key of every file in a directory, along with the asset manifest that maps
+// their paths to those keys. Files are hashed a chunk at a time and are not kept in memory.
+// With `[site] precompress`, compressible files also get a key per encoding; those variants are
+// only compressed when they are uploaded. Every key is written with the `AssetMetadata` of its
+// file. The `_headers` and `_redirects` files at the top of the directory aren't uploaded; they
+// are compiled into the rules of the asset manifest instead. With `[site] chunk_large_files`,
+// files too large for one value are stored as ordered chunks, and are not precompressed.
+pub fn directory_files(
     target: &Target,
     directory: &Path,
-) -> Result<(Vec<KeyValuePair>, AssetManifest, Vec<String>), failure::Error> {
+) -> Result<(Vec<KeyFile>, AssetManifest), failure::Error> {
     match &fs::metadata(directory) {
         Ok(file_type) if file_type.is_dir() => {
-            let mut upload_vec: Vec<KeyValuePair> = Vec::new();
+            let mut files: Vec<KeyFile> = Vec::new();
             let mut asset_manifest = AssetManifest::new();
-            let mut file_list: Vec<String> = Vec::new();
+            let precompress = target
+                .site
+                .as_ref()
+                .and_then(|site| site.precompress.clone())
+                .unwrap_or_default();
+            let chunk_large_files = target
+                .site
+                .as_ref()
+                .and_then(|site| site.chunk_large_files)
+                .unwrap_or(false);
+            let header_rules = HeaderRules::new(target.site.as_ref(), directory)?;
             let dir_walker = get_dir_iterator(target, directory)?;
             let spinner_style =
                 ProgressStyle::default_spinner().template("{spinner}   Preparing {msg}...");
@@ -83,34 +144,81 @@ pub fn directory_keys_values(
                 spinner.tick();
                 let entry = entry.unwrap();
                 let path = entry.path();
-                if path.is_file() {
+                if path.is_file() && !rules::is_rules_file(path, directory) {
                     spinner.set_message(&format!("{}", path.display()));
 
-                    file_list.push(path.to_str().unwrap().to_string());
-                    validate_file_size(&path)?;
-
-                    let value = std::fs::read(path)?;
-
-                    // Need to base64 encode value
-                    let b64_value = base64::encode(&value);
-
+                    let size = validate_file_size(&path, chunk_large_files)?;
+                    let digest = file_digest(&path)?;
+                    let asset_metadata = AssetMetadata {
+                        content_type: metadata::content_type(path)?,
+                        etag: format!("\"{}\"", digest),
+                        headers: header_rules.headers(path),
+                        content_encoding: None,
+                    };
+                    // the headers are hashed into the key along with the contents, so that a
+                    // file is uploaded again when only its headers change. Files without
+                    // headers keep the key of their contents alone, like before they had
+                    // metadata.
+                    let key_digest = if asset_metadata.headers.is_empty() {
+                        digest
+                    } else {
+                        get_digest(format!(
+                            "{}{}",
+                            digest,
+                            serde_json::to_string(&asset_metadata.headers)?
+                        ))
+                    };
                     let (url_safe_path, key) =
-                        generate_path_and_key(path, directory, Some(b64_value.clone()))?;
+                        path_and_key_with_digest(path, directory, key_digest)?;
 
                     validate_key_size(&key)?;
 
-                    upload_vec.push(KeyValuePair {
-                        key: key.clone(),
-                        value: b64_value,
-                        expiration: None,
-                        expiration_ttl: None,
-                        base64: Some(true),
+                    let mut asset = Asset::new(key.clone());
+                    if size > VALUE_MAX_SIZE {
+                        let metadata_value = asset_metadata.to_value(path)?;
+                        for chunk in chunk_files(&key, path, size, &metadata_value) {
+                            validate_key_size(&chunk.key)?;
+                            asset.chunks.push(chunk.key.clone());
+                            files.push(chunk);
+                        }
+                        asset.metadata = Some(asset_metadata);
+                        asset_manifest.insert(url_safe_path, asset);
+                        continue;
+                    }
+
+                    if encoding::is_compressible(path) {
+                        for encoding in &precompress {
+                            let encoded_key = format!("{}.{}", key, encoding.extension());
+                            validate_key_size(&encoded_key)?;
+
+                            files.push(KeyFile {
+                                key: encoded_key.clone(),
+                                path: path.to_path_buf(),
+                                size,
+                                offset: None,
+                                encoding: Some(*encoding),
+                                metadata: Some(asset_metadata.encoded(*encoding).to_value(path)?),
+                            });
+                            asset.encodings.insert(*encoding, encoded_key);
+                        }
+                    }
+
+                    files.push(KeyFile {
+                        key,
+                        path: path.to_path_buf(),
+                        size,
+                        offset: None,
+                        encoding: None,
+                        metadata: Some(asset_metadata.to_value(path)?),
                     });
+                    asset.metadata = Some(asset_metadata);
 
-                    asset_manifest.insert(url_safe_path, key);
+                    asset_manifest.insert(url_safe_path, asset);
                 }
             }
-            Ok((upload_vec, asset_manifest, file_list))
+            asset_manifest.set_rules(rules::compile(directory)?);
+
+            Ok((files, asset_manifest))
         }
         Ok(_file_type) => {
             // any other file types (files, symlinks)
@@ -126,19 +234,41 @@ pub fn directory_keys_values(
 // logic in validate_key_size()) because it duplicates the size checking the API already does--but
 // doing a preemptive check like this (before calling the API) will prevent partial bucket uploads
 // from happening.
-fn validate_file_size(path: &Path) -> Result<(), failure::Error> {
+// Returns the size of the file. Larger files are allowed when they are going to be chunked.
+fn validate_file_size(path: &Path, chunk_large_files: bool) -> Result<u64, failure::Error> {
     let metadata = fs::metadata(path)?;
     let file_len = metadata.len();
 
-    if file_len > VALUE_MAX_SIZE {
+    if file_len > VALUE_MAX_SIZE && !chunk_large_files {
         failure::bail!(
-            "File `{}` of {} bytes exceeds the maximum value size limit of {} bytes",
+            "File `{}` of {} bytes exceeds the maximum value size limit of {} bytes. Set `chunk_large_files = true` in [site] to store it in chunks.",
             path.display(),
             file_len,
             VALUE_MAX_SIZE
         );
     }
-    Ok(())
+    Ok(file_len)
+}
+
+// Splits a file into the ordered chunks it is stored as, each under the key of the file followed
+// by `.chunk<index>`, so that the keys of the chunks change with the contents of the file.
+fn chunk_files(key: &str, path: &Path, size: u64, metadata: &serde_json::Value) -> Vec<KeyFile> {
+    let mut chunks = Vec::new();
+    let mut offset = 0;
+    while offset < size {
+        let chunk_size = CHUNK_SIZE.min(size - offset);
+        chunks.push(KeyFile {
+            key: format!("{}.chunk{}", key, chunks.len()),
+            path: path.to_path_buf(),
+            size: chunk_size,
+            offset: Some(offset),
+            encoding: None,
+            metadata: Some(metadata.clone()),
+        });
+        offset += chunk_size;
+    }
+
+    chunks
 }
 
 fn validate_key_size(key: &str) -> Result<(), failure::Error> {
@@ -248,25 +378,72 @@ pub fn generate_path_and_key(
     directory: &Path,
     value: Option<String>,
 ) -> Result<(String, String), failure::Error> {
+    if let Some(value) = value {
+        return path_and_key_with_digest(path, directory, get_digest(value));
+    }
+
     // strip the bucket directory from both paths for ease of reference.
     let relative_path = path.strip_prefix(directory).unwrap();
+    let url_safe_path = generate_url_safe_path(relative_path)?;
+
+    Ok((url_safe_path.to_owned(), url_safe_path))
+}
 
+// Like `generate_path_and_key`, for a digest that was already computed from the file.
+fn path_and_key_with_digest(
+    path: &Path,
+    directory: &Path,
+    digest: String,
+) -> Result<(String, String), failure::Error> {
+    // strip the bucket directory from both paths for ease of reference.
+    let relative_path = path.strip_prefix(directory).unwrap();
     let url_safe_path = generate_url_safe_path(relative_path)?;
-    let path_with_hash = if let Some(value) = value {
-        let digest = get_digest(value);
-        // it is ok to truncate the digest here because
-        // we also include the file name in the asset manifest key
-        //
-        // the most important thing here is to detect changes
-        // of a single file to invalidate the cache and
-        // it's impossible to serve two different files with the same name
-        let digest = digest[0..10].to_string();
-        generate_path_with_hash(relative_path, digest)?
-    } else {
-        url_safe_path.to_owned()
-    };
+    // it is ok to truncate the digest here because
+    // we also include the file name in the asset manifest key
+    //
+    // the most important thing here is to detect changes
+    // of a single file to invalidate the cache and
+    // it's impossible to serve two different files with the same name
+    let digest = digest[0..10].to_string();
+
+    Ok((
+        url_safe_path,
+        generate_path_with_hash(relative_path, digest)?,
+    ))
+}
 
-    Ok((url_safe_path, path_with_hash))
+// The digest of the base64 encoding of a file, the same as `get_digest` of the whole encoding.
+// The file is encoded a chunk at a time; chunks are a multiple of 3 bytes long, so that their
+// encodings concatenate into the encoding of the whole file.
+fn file_digest(path: &Path) -> Result<String, failure::Error> {
+    let mut file = fs::File::open(path)?;
+    let mut hasher = XxHash64::default();
+    let mut chunk = vec![0; DIGEST_CHUNK_SIZE];
+
+    loop {
+        let len = read_chunk(&mut file, &mut chunk)?;
+        hasher.write(base64::encode(&chunk[..len]).as_bytes());
+        if len < chunk.len() {
+            break;
+        }
+    }
+
+    Ok(format!("{:x}", hasher.finish()))
+}
+
+// Fills `chunk` unless the end of the file comes first, and returns how much was read.
+fn read_chunk(file: &mut fs::File, chunk: &mut [u8]) -> Result<usize, std::io::Error> {
+    let mut filled = 0;
+    while filled < chunk.len() {
+        match file.read(&mut chunk[filled..]) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+
+    Ok(filled)
 }
 
 fn get_digest(value: String) -> String {
@@ -320,6 +497,7 @@ mod tests {
             site: Some(site),
             vars: None,
             text_blobs: None,
+            build: None,
         }
     }
 
@@ -370,7 +548,11 @@ mod tests {
             test_dir
         )))
         .unwrap();
-        let (_, _, file_list) = directory_keys_values(&target, Path::new(test_dir)).unwrap();
+        let (files, _) = directory_files(&target, Path::new(test_dir)).unwrap();
+        let file_list: Vec<String> = files
+            .iter()
+            .map(|file| file.path.to_str().unwrap().to_string())
+            .collect();
         if cfg!(windows) {
             assert!(!file_list.contains(&format!("{}\\.ignore_me.txt", test_dir)));
             assert!(file_list.contains(&format!("{}\\.well-known\\dontignoreme.txt", test_dir)));
@@ -537,6 +719,102 @@ mod tests {
         fs::remove_dir_all(test_dir).unwrap();
     }
 
+    #[test]
+    fn it_digests_files_like_their_whole_encoding() {
+        let test_dir = "test8";
+        // If test dir already exists, delete it.
+        if fs::metadata(test_dir).is_ok() {
+            fs::remove_dir_all(test_dir).unwrap();
+        }
+        fs::create_dir(test_dir).unwrap();
+
+        // spans a few chunks and ends in a partial one
+        let contents: Vec<u8> = (0..DIGEST_CHUNK_SIZE * 2 + 1)
+            .map(|i| (i % 251) as u8)
+            .collect();
+        let test_path = PathBuf::from(format!("{}/asset.bin", test_dir));
+        fs::write(&test_path, &contents).unwrap();
+
+        assert_eq!(
+            file_digest(&test_path).unwrap(),
+            get_digest(base64::encode(&contents))
+        );
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn it_adds_precompressed_variants_of_compressible_files() {
+        let mut site = Site::default();
+        site.bucket = PathBuf::from("fake");
+        site.precompress = Some(vec![Encoding::Br, Encoding::Gzip]);
+        let target = make_target(site);
+
+        let test_dir = "test9";
+        // If test dir already exists, delete it.
+        if fs::metadata(test_dir).is_ok() {
+            fs::remove_dir_all(test_dir).unwrap();
+        }
+        fs::create_dir(test_dir).unwrap();
+        fs::write(format!("{}/app.css", test_dir), "body { color: red; }").unwrap();
+        fs::write(format!("{}/logo.png", test_dir), [0x89, 0x50, 0x4e, 0x47]).unwrap();
+
+        let (files, asset_manifest) = directory_files(&target, Path::new(test_dir)).unwrap();
+
+        let css = asset_manifest.get("app.css").unwrap();
+        assert_eq!(css.encodings[&Encoding::Br], format!("{}.br", css.key));
+        assert_eq!(css.encodings[&Encoding::Gzip], format!("{}.gz", css.key));
+        assert!(asset_manifest.get("logo.png").unwrap().encodings.is_empty());
+
+        assert_eq!(files.len(), 4);
+        let gzipped = files
+            .iter()
+            .find(|file| file.key == css.encodings[&Encoding::Gzip])
+            .unwrap();
+        assert_eq!(gzipped.encoding, Some(Encoding::Gzip));
+        let gzipped_metadata = gzipped.metadata.as_ref().unwrap();
+        assert_eq!(gzipped_metadata["contentType"], "text/css; charset=utf-8");
+        assert_eq!(gzipped_metadata["contentEncoding"], "gzip");
+        assert_eq!(gzipped.path, PathBuf::from(format!("{}/app.css", test_dir)));
+
+        fs::remove_dir_all(test_dir).unwrap();
+    }
+
+    #[test]
+    fn it_splits_large_files_into_contiguous_chunks() {
+        let size = VALUE_MAX_SIZE + 1;
+        let metadata = serde_json::json!({ "contentType": "video/mp4" });
+        let chunks = chunk_files(
+            "video.1a2b3c4d5e.mp4",
+            Path::new("public/video.mp4"),
+            size,
+            &metadata,
+        );
+
+        assert_eq!(
+            chunks.iter().map(|chunk| &chunk.key).collect::<Vec<_>>(),
+            vec![
+                "video.1a2b3c4d5e.mp4.chunk0",
+                "video.1a2b3c4d5e.mp4.chunk1",
+                "video.1a2b3c4d5e.mp4.chunk2",
+            ]
+        );
+        assert_eq!(
+            chunks
+                .iter()
+                .map(|chunk| (chunk.offset.unwrap(), chunk.size))
+                .collect::<Vec<_>>(),
+            vec![
+                (0, CHUNK_SIZE),
+                (CHUNK_SIZE, CHUNK_SIZE),
+                (2 * CHUNK_SIZE, size - 2 * CHUNK_SIZE),
+            ]
+        );
+        assert!(chunks
+            .iter()
+            .all(|chunk| chunk.metadata.as_ref() == Some(&metadata)));
+    }
+
     #[test]
     fn it_inserts_hash_before_extension() {
         let value = "<h1>Hello World!</h1>";
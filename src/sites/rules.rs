@@ -0,0 +1,386 @@
+use std::collections::{BTreeMap, HashSet};
+use std::fs;
+use std::path::Path;
+
+use http::header::{HeaderName, HeaderValue};
+use serde::{Deserialize, Serialize};
+
+pub const HEADERS_FILE_NAME: &str = "_headers";
+pub const REDIRECTS_FILE_NAME: &str = "_redirects";
+
+const REDIRECT_STATUSES: &[u16] = &[200, 301, 302, 303, 307, 308, 404];
+const DEFAULT_REDIRECT_STATUS: u16 = 301;
+// the placeholder that stands for what a `*` matched
+const SPLAT: &str = "splat";
+
+/// The `_headers` and `_redirects` files of a Workers Site, compiled for the worker.
+#[derive(Clone, Debug, Default, Deserialize, PartialEq, Serialize)]
+pub struct SiteRules {
+    pub headers: Vec<PathHeaders>,
+    pub redirects: Vec<Redirect>,
+}
+
+/// The headers to serve the paths matching `pattern` with.
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct PathHeaders {
+    pub pattern: String,
+    pub headers: BTreeMap<String, String>,
+}
+
+#[derive(Clone, Debug, Deserialize, PartialEq, Serialize)]
+pub struct Redirect {
+    pub from: String,
+    pub to: String,
+    pub status: u16,
+}
+
+// Whether `path` is one of the rules files, which are only read at the top of the bucket.
+pub fn is_rules_file(path: &Path, directory: &Path) -> bool {
+    path == directory.join(HEADERS_FILE_NAME) || path == directory.join(REDIRECTS_FILE_NAME)
+}
+
+// Compiles the rules files at the top of `directory`, reporting every line that can't be parsed.
+pub fn compile(directory: &Path) -> Result<Option<SiteRules>, failure::Error> {
+    let headers_path = directory.join(HEADERS_FILE_NAME);
+    let redirects_path = directory.join(REDIRECTS_FILE_NAME);
+    if !headers_path.is_file() && !redirects_path.is_file() {
+        return Ok(None);
+    }
+
+    let mut rules = SiteRules::default();
+    let mut errors = Vec::new();
+    if headers_path.is_file() {
+        let contents = fs::read_to_string(&headers_path)?;
+        match parse_headers(&headers_path, &contents) {
+            Ok(headers) => rules.headers = headers,
+            Err(e) => errors.extend(e),
+        }
+    }
+    if redirects_path.is_file() {
+        let contents = fs::read_to_string(&redirects_path)?;
+        match parse_redirects(&redirects_path, &contents) {
+            Ok(redirects) => rules.redirects = redirects,
+            Err(e) => errors.extend(e),
+        }
+    }
+
+    if !errors.is_empty() {
+        failure::bail!(
+            "Your Workers Site rules have {} error(s)\n{}",
+            errors.len(),
+            errors.join("\n")
+        )
+    }
+
+    Ok(Some(rules))
+}
+
+// `_headers` is made of path patterns, each followed by indented `Name: value` lines.
+fn parse_headers(file: &Path, contents: &str) -> Result<Vec<PathHeaders>, Vec<String>> {
+    let mut rules: Vec<PathHeaders> = Vec::new();
+    // the line of each pattern, and whether any header followed it
+    let mut pattern_lines: Vec<(usize, bool)> = Vec::new();
+    let mut errors = Vec::new();
+    let mut error = |line_number: usize, message: String| {
+        errors.push(format!("{}:{}: {}", file.display(), line_number, message))
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        if !line.starts_with(char::is_whitespace) {
+            if let Err(e) = validate_pattern(trimmed) {
+                error(line_number, e);
+            }
+            rules.push(PathHeaders {
+                pattern: trimmed.to_string(),
+                headers: BTreeMap::new(),
+            });
+            pattern_lines.push((line_number, false));
+            continue;
+        }
+
+        let rule = match rules.last_mut() {
+            Some(rule) => rule,
+            None => {
+                error(
+                    line_number,
+                    "headers need a path pattern on a line of its own before them".to_string(),
+                );
+                continue;
+            }
+        };
+        if let Some((_, has_headers)) = pattern_lines.last_mut() {
+            *has_headers = true;
+        }
+        let colon = match trimmed.find(':') {
+            Some(colon) => colon,
+            None => {
+                error(
+                    line_number,
+                    format!("expected `Name: value`, found \"{}\"", trimmed),
+                );
+                continue;
+            }
+        };
+        let name = trimmed[..colon].trim();
+        let value = trimmed[colon + 1..].trim();
+        if HeaderName::from_bytes(name.as_bytes()).is_err() {
+            error(
+                line_number,
+                format!("\"{}\" is not a valid header name", name),
+            );
+        } else if value.is_empty() || HeaderValue::from_str(value).is_err() {
+            error(
+                line_number,
+                format!("\"{}\" is not a valid value for {}", value, name),
+            );
+        } else {
+            // a header that is set more than once is served with every value
+            rule.headers
+                .entry(name.to_string())
+                .and_modify(|values| {
+                    values.push_str(", ");
+                    values.push_str(value);
+                })
+                .or_insert_with(|| value.to_string());
+        }
+    }
+
+    for (rule, (line_number, has_headers)) in rules.iter().zip(pattern_lines) {
+        if !has_headers {
+            error(line_number, format!("{} has no headers", rule.pattern));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(rules)
+    } else {
+        Err(errors)
+    }
+}
+
+// `_redirects` has a redirect per line: `from to [status]`.
+fn parse_redirects(file: &Path, contents: &str) -> Result<Vec<Redirect>, Vec<String>> {
+    let mut redirects = Vec::new();
+    let mut errors = Vec::new();
+
+    for (i, line) in contents.lines().enumerate() {
+        let line_number = i + 1;
+        let trimmed = line.trim();
+        if trimmed.is_empty() || trimmed.starts_with('#') {
+            continue;
+        }
+
+        match parse_redirect(trimmed) {
+            Ok(redirect) => redirects.push(redirect),
+            Err(e) => errors.push(format!("{}:{}: {}", file.display(), line_number, e)),
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(redirects)
+    } else {
+        Err(errors)
+    }
+}
+
+fn parse_redirect(line: &str) -> Result<Redirect, String> {
+    let fields: Vec<&str> = line.split_whitespace().collect();
+    let (from, to, status) = match fields.as_slice() {
+        [from, to] => (*from, *to, DEFAULT_REDIRECT_STATUS),
+        [from, to, status] => match status.parse::<u16>() {
+            Ok(status) if REDIRECT_STATUSES.contains(&status) => (*from, *to, status),
+            _ => {
+                return Err(format!(
+                    "\"{}\" is not a supported status, use one of {}",
+                    status,
+                    REDIRECT_STATUSES
+                        .iter()
+                        .map(u16::to_string)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                ))
+            }
+        },
+        _ => {
+            return Err(format!(
+                "expected `from to [status]`, found {} fields",
+                fields.len()
+            ))
+        }
+    };
+
+    let placeholders = validate_pattern(from)?;
+    validate_url(to)?;
+    for placeholder in placeholder_names(to) {
+        if !placeholders.contains(placeholder) {
+            return Err(format!(
+                ":{} is used in {} but not matched by {}",
+                placeholder, to, from
+            ));
+        }
+    }
+
+    Ok(Redirect {
+        from: from.to_string(),
+        to: to.to_string(),
+        status,
+    })
+}
+
+// Checks a path pattern, and returns the names of its placeholders, including `splat` if it
+// ends in a `*`.
+fn validate_pattern(pattern: &str) -> Result<HashSet<&str>, String> {
+    validate_url(pattern)?;
+    if let Some(star) = pattern.find('*') {
+        if star != pattern.len() - 1 {
+            return Err(format!(
+                "{} can only have one `*`, at the end of the pattern",
+                pattern
+            ));
+        }
+    }
+
+    let mut placeholders = HashSet::new();
+    for name in placeholder_names(pattern) {
+        if name == SPLAT {
+            return Err(format!(
+                "{} can't have a :{} placeholder, end the pattern with `*` instead",
+                pattern, SPLAT
+            ));
+        }
+        if !placeholders.insert(name) {
+            return Err(format!("{} has the placeholder :{} twice", pattern, name));
+        }
+    }
+    if pattern.ends_with('*') {
+        placeholders.insert(SPLAT);
+    }
+
+    Ok(placeholders)
+}
+
+fn validate_url(url: &str) -> Result<(), String> {
+    if url.starts_with('/') || url.starts_with("https://") || url.starts_with("http://") {
+        Ok(())
+    } else {
+        Err(format!(
+            "{} should be a path starting with / or a URL starting with https://",
+            url
+        ))
+    }
+}
+
+// The names of the `:placeholder`s in a pattern or URL.
+fn placeholder_names(url: &str) -> Vec<&str> {
+    let mut names = Vec::new();
+    for (i, _) in url.match_indices(':') {
+        let rest = &url[i + 1..];
+        let name = match rest.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')) {
+            Some(end) => &rest[..end],
+            None => rest,
+        };
+        // skips the `:` of `https://` and of ports
+        if name.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') {
+            names.push(name);
+        }
+    }
+
+    names
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn it_parses_redirects() {
+        let redirects = parse_redirects(
+            Path::new("_redirects"),
+            "# moved\n/home / 301\n\n/blog/* /news/:splat\n/users/:id https://example.com/u/:id 302\n",
+        )
+        .unwrap();
+
+        assert_eq!(
+            redirects,
+            vec![
+                Redirect {
+                    from: "/home".to_string(),
+                    to: "/".to_string(),
+                    status: 301,
+                },
+                Redirect {
+                    from: "/blog/*".to_string(),
+                    to: "/news/:splat".to_string(),
+                    status: DEFAULT_REDIRECT_STATUS,
+                },
+                Redirect {
+                    from: "/users/:id".to_string(),
+                    to: "https://example.com/u/:id".to_string(),
+                    status: 302,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_redirects_with_their_line() {
+        let errors = parse_redirects(
+            Path::new("public/_redirects"),
+            "/old /new 418\n/a/*/b /c\n/posts/:id /p/:slug\nnews /news\n/x\n/ok /fine\n",
+        )
+        .unwrap_err();
+
+        assert_eq!(errors.len(), 5);
+        assert!(errors[0].starts_with("public/_redirects:1: \"418\""));
+        assert!(errors[1].starts_with("public/_redirects:2:"));
+        assert!(errors[2].starts_with("public/_redirects:3: :slug"));
+        assert!(errors[3].starts_with("public/_redirects:4:"));
+        assert!(errors[4].starts_with("public/_redirects:5: expected"));
+    }
+
+    #[test]
+    fn it_parses_headers() {
+        let headers = parse_headers(
+            Path::new("_headers"),
+            "/*\n  X-Frame-Options: DENY\n  Link: </a.css>; rel=preload\n  Link: </b.js>; rel=preload\n\n/assets/*\n  Cache-Control: public, max-age=31536000\n",
+        )
+        .unwrap();
+
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers[0].pattern, "/*");
+        assert_eq!(headers[0].headers["X-Frame-Options"], "DENY");
+        assert_eq!(
+            headers[0].headers["Link"],
+            "</a.css>; rel=preload, </b.js>; rel=preload"
+        );
+        assert_eq!(
+            headers[1].headers["Cache-Control"],
+            "public, max-age=31536000"
+        );
+    }
+
+    #[test]
+    fn it_reports_invalid_headers_with_their_line() {
+        let errors = parse_headers(
+            Path::new("_headers"),
+            "  X-Early: yes\n/*\n  Bad Name: value\n  no colon\n/empty\n",
+        )
+        .unwrap_err();
+
+        assert_eq!(
+            errors,
+            vec![
+                "_headers:1: headers need a path pattern on a line of its own before them",
+                "_headers:3: \"Bad Name\" is not a valid header name",
+                "_headers:4: expected `Name: value`, found \"no colon\"",
+                "_headers:5: /empty has no headers",
+            ]
+        );
+    }
+}
@@ -1,12 +1,12 @@
 use std::collections::HashSet;
 use std::path::Path;
 
-use cloudflare::endpoints::workerskv::write_bulk::KeyValuePair;
-
-use super::directory_keys_values;
+use super::directory_files;
+use super::generations::is_generation_key;
 use super::manifest::AssetManifest;
 use crate::commands::kv;
 use crate::http;
+use crate::kv::bulk::KeyFile;
 use crate::kv::key::KeyList;
 use crate::settings::global_user::GlobalUser;
 use crate::settings::toml::Target;
@@ -17,58 +17,79 @@ pub fn sync(
     user: &GlobalUser,
     namespace_id: &str,
     path: &Path,
-) -> Result<(Vec<KeyValuePair>, Vec<String>, AssetManifest), failure::Error> {
+) -> Result<(Vec<KeyFile>, Vec<String>, AssetManifest), failure::Error> {
     kv::validate_target(target)?;
-    // First, find all changed files in given local directory (aka files that are now stale
-    // in Workers KV).
+    let remote_keys = remote_keys(target, user, namespace_id)?;
+    diff(target, path, &remote_keys)
+}
 
-    // Get remote keys, which contain the hash of the file (value) as the suffix.
-    // Turn it into a HashSet. This will be used by upload() to figure out which
-    // files to exclude from upload (because their current version already exists in
-    // the Workers KV remote).
+// Get remote keys, which contain the hash of the file (value) as the suffix.
+// Turn it into a HashSet. This will be used by diff() to figure out which
+// files to exclude from upload (because their current version already exists in
+// the Workers KV remote). The generations recorded in the namespace aren't files, so they
+// are left out.
+pub fn remote_keys(
+    target: &Target,
+    user: &GlobalUser,
+    namespace_id: &str,
+) -> Result<HashSet<String>, failure::Error> {
     let client = http::cf_v4_client(&user)?;
     let remote_keys_iter = KeyList::new(target, client, namespace_id, None)?;
     let mut remote_keys: HashSet<String> = HashSet::new();
     for remote_key in remote_keys_iter {
         match remote_key {
             Ok(remote_key) => {
-                remote_keys.insert(remote_key.name);
+                if !is_generation_key(&remote_key.name) {
+                    remote_keys.insert(remote_key.name);
+                }
             }
             Err(e) => failure::bail!(kv::format_error(e)),
         }
     }
 
-    let (pairs, asset_manifest, _): (Vec<KeyValuePair>, AssetManifest, _) =
-        directory_keys_values(target, path)?;
+    Ok(remote_keys)
+}
 
-    let to_upload = filter_files(pairs.clone(), &remote_keys);
+// Compares the files in the given local directory with the keys in Workers KV: the files
+// that aren't in `remote_keys` need to be uploaded, and the keys that no longer belong to a
+// local file are stale.
+pub fn diff(
+    target: &Target,
+    path: &Path,
+    remote_keys: &HashSet<String>,
+) -> Result<(Vec<KeyFile>, Vec<String>, AssetManifest), failure::Error> {
+    // Files are only hashed here; they're read again when they are uploaded.
+    let (files, asset_manifest) = directory_files(target, path)?;
 
     // Now delete files from Workers KV that exist in remote but no longer exist locally.
-    // Get local keys
-    let mut local_keys: HashSet<_> = HashSet::new();
-    for pair in pairs.iter() {
-        local_keys.insert(pair.key.clone());
-    }
-
-    // Find keys that are present in remote but not present in local, and
-    // stage them for deletion.
-    let to_delete: Vec<_> = remote_keys
-        .difference(&local_keys)
-        .map(|key| key.to_owned())
-        .collect();
+    let to_delete = stale_keys(&files, remote_keys);
+    let to_upload = filter_files(files, remote_keys);
 
     StdErr::success("Success");
     Ok((to_upload, to_delete, asset_manifest))
 }
 
-fn filter_files(pairs: Vec<KeyValuePair>, already_uploaded: &HashSet<String>) -> Vec<KeyValuePair> {
-    let mut filtered_pairs: Vec<KeyValuePair> = Vec::new();
-    for pair in pairs {
-        if !already_uploaded.contains(&pair.key) {
-            filtered_pairs.push(pair);
+// Find keys that are present in remote but not present in local, and stage them for deletion.
+// Every chunk of a chunked file has a key of its own, so the chunks of a file's old contents
+// are stale along with the rest of them.
+fn stale_keys(files: &[KeyFile], remote_keys: &HashSet<String>) -> Vec<String> {
+    let local_keys: HashSet<&String> = files.iter().map(|file| &file.key).collect();
+
+    remote_keys
+        .iter()
+        .filter(|key| !local_keys.contains(key))
+        .map(|key| key.to_owned())
+        .collect()
+}
+
+fn filter_files(files: Vec<KeyFile>, already_uploaded: &HashSet<String>) -> Vec<KeyFile> {
+    let mut filtered_files: Vec<KeyFile> = Vec::new();
+    for file in files {
+        if !already_uploaded.contains(&file.key) {
+            filtered_files.push(file);
         }
     }
-    filtered_pairs
+    filtered_files
 }
 
 #[cfg(test)]
@@ -76,7 +97,7 @@ mod tests {
     use super::*;
     use crate::sites::generate_path_and_key;
     use std::collections::HashSet;
-    use std::path::Path;
+    use std::path::{Path, PathBuf};
 
     #[test]
     fn it_can_filter_preexisting_files() {
@@ -97,43 +118,77 @@ mod tests {
         exclude_keys.insert(key_b_old);
 
         // local files (with b updated) to upload
-        let pairs_to_upload = vec![
-            KeyValuePair {
+        let files_to_upload = vec![
+            KeyFile {
                 key: key_a_old,
-                value: "old".to_string(), // This value remains unchanged
-                expiration_ttl: None,
-                expiration: None,
-                base64: None,
+                path: PathBuf::from("/a"), // This file remains unchanged
+                size: 3,
+                offset: None,
+                encoding: None,
+                metadata: None,
             },
-            KeyValuePair {
+            KeyFile {
                 key: key_b_new.clone(),
-                value: "new".to_string(), // Note this pair has a new value
-                expiration_ttl: None,
-                expiration: None,
-                base64: None,
+                path: PathBuf::from("/b"), // Note this file has new contents
+                size: 3,
+                offset: None,
+                encoding: None,
+                metadata: None,
             },
         ];
 
-        let expected = vec![KeyValuePair {
+        let expected = vec![KeyFile {
             key: key_b_new,
-            value: "new".to_string(),
-            expiration_ttl: None,
-            expiration: None,
-            base64: None,
+            path: PathBuf::from("/b"),
+            size: 3,
+            offset: None,
+            encoding: None,
+            metadata: None,
         }];
-        let actual = filter_files(pairs_to_upload, &exclude_keys);
-        check_kv_pairs_equality(expected, actual);
+        let actual = filter_files(files_to_upload, &exclude_keys);
+        assert_eq!(expected, actual);
     }
 
-    fn check_kv_pairs_equality(expected: Vec<KeyValuePair>, actual: Vec<KeyValuePair>) {
-        assert_eq!(expected.len(), actual.len());
-        for (idx, pair) in expected.into_iter().enumerate() {
-            // Ensure the expected key and value was returned in the filtered pair list
-            // Awkward field-by-field comparison below courtesy of not yet implementing
-            // PartialEq for KeyValuePair in cloudflare-rs :)
-            // TODO: (gabbi) Implement PartialEq for KeyValuePair in cloudflare-rs.
-            assert_eq!(pair.key, actual[idx].key);
-            assert_eq!(pair.value, actual[idx].value);
-        }
+    #[test]
+    fn it_replaces_the_chunks_of_changed_files() {
+        let chunk = |key: &str, offset: u64| KeyFile {
+            key: key.to_string(),
+            path: PathBuf::from("/video.mp4"),
+            size: 10,
+            offset: Some(offset),
+            encoding: None,
+            metadata: None,
+        };
+
+        // the file changed after only the first chunk of its new contents was uploaded
+        let remote_keys: HashSet<String> = vec![
+            "video.old.mp4.chunk0",
+            "video.old.mp4.chunk1",
+            "video.new.mp4.chunk0",
+        ]
+        .into_iter()
+        .map(String::from)
+        .collect();
+        let files = vec![
+            chunk("video.new.mp4.chunk0", 0),
+            chunk("video.new.mp4.chunk1", 10),
+            chunk("video.new.mp4.chunk2", 20),
+        ];
+
+        let mut to_delete = stale_keys(&files, &remote_keys);
+        to_delete.sort();
+        assert_eq!(
+            to_delete,
+            vec!["video.old.mp4.chunk0", "video.old.mp4.chunk1"]
+        );
+
+        let to_upload = filter_files(files, &remote_keys);
+        assert_eq!(
+            to_upload,
+            vec![
+                chunk("video.new.mp4.chunk1", 10),
+                chunk("video.new.mp4.chunk2", 20),
+            ]
+        );
     }
 }
@@ -1,51 +1,71 @@
+mod modules_worker;
 mod plain_text;
 mod project_assets;
 mod text_blob;
 mod wasm_module;
 
 use reqwest::blocking::multipart::{Form, Part};
+use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::Path;
 use std::path::PathBuf;
 
-use crate::settings::binding;
+use crate::settings::binding::{self, Binding};
 use crate::settings::metadata::Metadata;
-use crate::settings::toml::{Target, TargetType};
+use crate::settings::toml::{
+    Builder, KvNamespace, ModuleRule, ModuleType, Target, TargetType, UploadFormat,
+};
 use crate::sites::AssetManifest;
 use crate::wranglerjs;
 
+pub use project_assets::{ProjectAssets, ScriptFormat};
+
+use modules_worker::Module;
 use plain_text::PlainText;
-use project_assets::ProjectAssets;
 use text_blob::TextBlob;
 use wasm_module::WasmModule;
 
 // TODO: https://github.com/cloudflare/wrangler/issues/1083
 use super::{krate, Package};
 
+const STATIC_CONTENT_MANIFEST: &str = "__STATIC_CONTENT_MANIFEST";
+// the asset manifest with the precompressed variants and metadata of every file
+const STATIC_CONTENT_ASSETS: &str = "__STATIC_CONTENT_ASSETS";
+// the compiled `_headers` and `_redirects` of the site
+const STATIC_CONTENT_RULES: &str = "__STATIC_CONTENT_RULES";
+
 pub fn build(
     target: &Target,
     asset_manifest: Option<AssetManifest>,
     session_config: Option<serde_json::Value>,
 ) -> Result<Form, failure::Error> {
-    let target_type = &target.target_type;
-    let kv_namespaces = &target.kv_namespaces;
-    let mut text_blobs: Vec<TextBlob> = Vec::new();
-    let mut plain_texts: Vec<PlainText> = Vec::new();
-    let mut wasm_modules: Vec<WasmModule> = Vec::new();
+    let assets = project_assets(target, asset_manifest)?;
 
-    if let Some(blobs) = &target.text_blobs {
-        for (key, blob_path) in blobs.iter() {
-            let blob = fs::read_to_string(blob_path)?;
-            text_blobs.push(TextBlob::new(blob, key.clone())?);
-        }
-    }
+    build_form(&assets, session_config)
+}
 
-    if let Some(vars) = &target.vars {
-        for (key, value) in vars.iter() {
-            plain_texts.push(PlainText::new(key.clone(), value.clone())?)
-        }
+// Collects the script, modules and bindings that make up the upload form for a target.
+pub fn project_assets(
+    target: &Target,
+    asset_manifest: Option<AssetManifest>,
+) -> Result<ProjectAssets, failure::Error> {
+    if let Some(Builder { upload, .. }) = &target.build {
+        return match upload {
+            UploadFormat::ServiceWorker { main, dir } => {
+                service_worker_assets(target, dir.join(main), asset_manifest)
+            }
+            UploadFormat::Modules { main, dir, rules } => {
+                modules_assets(target, main, dir, rules, asset_manifest)
+            }
+        };
     }
 
+    let target_type = &target.target_type;
+    let kv_namespaces = &target.kv_namespaces;
+    let mut text_blobs = text_blobs(target)?;
+    let plain_texts = plain_texts(target)?;
+    let mut wasm_modules: Vec<WasmModule> = Vec::new();
+
     match target_type {
         TargetType::Rust => {
             log::info!("Rust project detected. Publishing...");
@@ -68,7 +88,7 @@ pub fn build(
                 plain_texts,
             )?;
 
-            build_form(&assets, session_config)
+            Ok(assets)
         }
         TargetType::JavaScript => {
             log::info!("JavaScript project detected. Publishing...");
@@ -85,7 +105,7 @@ pub fn build(
                 plain_texts,
             )?;
 
-            build_form(&assets, session_config)
+            Ok(assets)
         }
         TargetType::Webpack => {
             log::info!("webpack project detected. Publishing...");
@@ -103,11 +123,11 @@ pub fn build(
             }
 
             if let Some(asset_manifest) = asset_manifest {
-                log::info!("adding __STATIC_CONTENT_MANIFEST");
-                let binding = "__STATIC_CONTENT_MANIFEST".to_string();
-                let asset_manifest_blob = get_asset_manifest_blob(asset_manifest)?;
-                let text_blob = TextBlob::new(asset_manifest_blob, binding)?;
-                text_blobs.push(text_blob);
+                for (binding, asset_manifest_blob) in get_asset_manifest_blobs(asset_manifest)? {
+                    log::info!("adding {}", binding);
+                    let text_blob = TextBlob::new(asset_manifest_blob, binding)?;
+                    text_blobs.push(text_blob);
+                }
             }
 
             let assets = ProjectAssets::new(
@@ -118,17 +138,150 @@ pub fn build(
                 plain_texts,
             )?;
 
-            build_form(&assets, session_config)
+            Ok(assets)
+        }
+    }
+}
+
+// The script a custom `[build]` command produced, uploaded as is.
+fn service_worker_assets(
+    target: &Target,
+    script_path: PathBuf,
+    asset_manifest: Option<AssetManifest>,
+) -> Result<ProjectAssets, failure::Error> {
+    log::info!("Custom build detected. Publishing...");
+    if !script_path.is_file() {
+        failure::bail!(
+            "Could not find {}. Run your build command, or check [build.upload] in your wrangler.toml",
+            script_path.display()
+        )
+    }
+
+    let mut text_blobs = text_blobs(target)?;
+    if let Some(asset_manifest) = asset_manifest {
+        for (binding, asset_manifest_blob) in get_asset_manifest_blobs(asset_manifest)? {
+            log::info!("adding {}", binding);
+            text_blobs.push(TextBlob::new(asset_manifest_blob, binding)?);
         }
     }
+
+    ProjectAssets::new(
+        script_path,
+        Vec::new(),
+        target.kv_namespaces.to_vec(),
+        text_blobs,
+        plain_texts(target)?,
+    )
 }
 
-fn get_asset_manifest_blob(asset_manifest: AssetManifest) -> Result<String, failure::Error> {
-    let asset_manifest = serde_json::to_string(&asset_manifest)?;
-    Ok(asset_manifest)
+fn modules_assets(
+    target: &Target,
+    main: &Path,
+    dir: &Path,
+    rules: &[ModuleRule],
+    asset_manifest: Option<AssetManifest>,
+) -> Result<ProjectAssets, failure::Error> {
+    log::info!("Modules worker detected. Publishing...");
+    if target.text_blobs.is_some() {
+        failure::bail!("text_blobs are not supported with the \"modules\" upload format, import the file as a Text module instead")
+    }
+
+    let (main_module_name, main_module_path, mut modules) =
+        modules_worker::collect(dir, main, rules)?;
+
+    if let Some(asset_manifest) = asset_manifest {
+        for (name, asset_manifest_blob) in get_asset_manifest_blobs(asset_manifest)? {
+            log::info!("adding {} module", name);
+            modules.push(Module::new(
+                name,
+                ModuleType::Text,
+                asset_manifest_blob.into_bytes(),
+            ));
+        }
+    }
+
+    Ok(ProjectAssets::new_modules(
+        main_module_name,
+        main_module_path,
+        modules,
+        target.kv_namespaces.to_vec(),
+        plain_texts(target)?,
+    ))
+}
+
+// Swaps the kv_namespaces, vars and text_blobs of `assets` for the ones configured for
+// `target`, keeping the script, its modules, its wasm and its asset manifest as they were built.
+pub fn rebind(mut assets: ProjectAssets, target: &Target) -> Result<ProjectAssets, failure::Error> {
+    if assets.format() == ScriptFormat::Modules && target.text_blobs.is_some() {
+        failure::bail!("text_blobs are not supported with the \"modules\" upload format, import the file as a Text module instead")
+    }
+
+    let mut text_blobs = text_blobs(target)?;
+    text_blobs.extend(assets.text_blobs.drain(..).filter(|blob| {
+        blob.binding == STATIC_CONTENT_MANIFEST
+            || blob.binding == STATIC_CONTENT_ASSETS
+            || blob.binding == STATIC_CONTENT_RULES
+    }));
+
+    assets.text_blobs = text_blobs;
+    assets.kv_namespaces = target.kv_namespaces.to_vec();
+    assets.plain_texts = plain_texts(target)?;
+
+    Ok(assets)
+}
+
+fn text_blobs(target: &Target) -> Result<Vec<TextBlob>, failure::Error> {
+    let mut text_blobs: Vec<TextBlob> = Vec::new();
+
+    if let Some(blobs) = &target.text_blobs {
+        for (key, blob_path) in blobs.iter() {
+            let blob = fs::read_to_string(blob_path)?;
+            text_blobs.push(TextBlob::new(blob, key.clone())?);
+        }
+    }
+
+    Ok(text_blobs)
+}
+
+fn plain_texts(target: &Target) -> Result<Vec<PlainText>, failure::Error> {
+    let mut plain_texts: Vec<PlainText> = Vec::new();
+
+    if let Some(vars) = &target.vars {
+        for (key, value) in vars.iter() {
+            plain_texts.push(PlainText::new(key.clone(), value.clone())?)
+        }
+    }
+
+    Ok(plain_texts)
+}
+
+// The asset manifest by binding: the flat path to key map that every Workers Site reads, the
+// whole manifest if some files are precompressed or chunked, and
+// the rules of the site's `_headers` and `_redirects`, if it has any.
+fn get_asset_manifest_blobs(
+    asset_manifest: AssetManifest,
+) -> Result<Vec<(String, String)>, failure::Error> {
+    let mut blobs = vec![(
+        STATIC_CONTENT_MANIFEST.to_string(),
+        serde_json::to_string(&asset_manifest.original_keys())?,
+    )];
+    if !asset_manifest.is_flat() {
+        blobs.push((
+            STATIC_CONTENT_ASSETS.to_string(),
+            serde_json::to_string(&asset_manifest)?,
+        ));
+    }
+    if let Some(rules) = asset_manifest.rules() {
+        blobs.push((
+            STATIC_CONTENT_RULES.to_string(),
+            serde_json::to_string(rules)?,
+        ));
+    }
+
+    Ok(blobs)
 }
 
-fn build_form(
+pub fn build_form(
     assets: &ProjectAssets,
     session_config: Option<serde_json::Value>,
 ) -> Result<Form, failure::Error> {
@@ -149,7 +302,21 @@ fn build_form(
 }
 
 fn add_files(mut form: Form, assets: &ProjectAssets) -> Result<Form, failure::Error> {
-    form = form.file(assets.script_name(), assets.script_path())?;
+    match assets.format() {
+        ScriptFormat::ServiceWorker => {
+            form = form.file(assets.script_name(), assets.script_path())?;
+        }
+        ScriptFormat::Modules => {
+            let main_module = Part::file(assets.script_path())?
+                .file_name(assets.script_name())
+                .mime_str(ModuleType::ESModule.content_type())?;
+            form = form.part(assets.script_name(), main_module);
+
+            for module in &assets.modules {
+                form = form.part(module.name(), module.part()?);
+            }
+        }
+    }
 
     for wasm_module in &assets.wasm_modules {
         form = form.file(wasm_module.filename(), wasm_module.path())?;
@@ -166,11 +333,175 @@ fn add_files(mut form: Form, assets: &ProjectAssets) -> Result<Form, failure::Er
     Ok(form)
 }
 
-fn add_metadata(mut form: Form, assets: &ProjectAssets) -> Result<Form, failure::Error> {
-    let metadata_json = serde_json::json!(&Metadata {
-        body_part: assets.script_name(),
+// Writes every part of the upload form to `dir`, one file per part, so that the
+// exact contents of an upload can be inspected without sending it.
+pub fn write_parts(assets: &ProjectAssets, dir: &Path) -> Result<(), failure::Error> {
+    fs::create_dir_all(dir)?;
+
+    let metadata_json = serde_json::to_string_pretty(&metadata(assets))?;
+    fs::write(dir.join("metadata.json"), metadata_json)?;
+
+    let script_path = assets.script_path();
+    match assets.format() {
+        ScriptFormat::ServiceWorker => {
+            fs::copy(&script_path, dir.join(part_file_name(&script_path)?))?;
+        }
+        ScriptFormat::Modules => {
+            let modules_dir = dir.join(MODULES_DIR_NAME);
+            write_module(
+                &modules_dir,
+                &assets.script_name(),
+                &fs::read(&script_path)?,
+            )?;
+
+            let mut module_entries = Vec::new();
+            for module in &assets.modules {
+                write_module(&modules_dir, &module.name(), module.content())?;
+                module_entries.push(ModuleEntry {
+                    name: module.name(),
+                    module_type: module.module_type(),
+                });
+            }
+            fs::write(
+                dir.join("modules.json"),
+                serde_json::to_string_pretty(&module_entries)?,
+            )?;
+        }
+    }
+
+    for wasm_module in &assets.wasm_modules {
+        let wasm_path = wasm_module.path();
+        fs::copy(&wasm_path, dir.join(part_file_name(&wasm_path)?))?;
+    }
+
+    for text_blob in &assets.text_blobs {
+        fs::write(dir.join(&text_blob.binding), &text_blob.data)?;
+    }
+
+    Ok(())
+}
+
+// Reads the parts written by `write_parts` back into the assets they were written from.
+pub fn read_parts(dir: &Path) -> Result<ProjectAssets, failure::Error> {
+    let metadata_json = fs::read_to_string(dir.join("metadata.json"))?;
+    let metadata: Metadata = serde_json::from_str(&metadata_json)?;
+
+    let mut wasm_modules: Vec<WasmModule> = Vec::new();
+    let mut kv_namespaces: Vec<KvNamespace> = Vec::new();
+    let mut text_blobs: Vec<TextBlob> = Vec::new();
+    let mut plain_texts: Vec<PlainText> = Vec::new();
+
+    for binding in metadata.bindings {
+        match binding {
+            Binding::WasmModule { name, part } => {
+                wasm_modules.push(WasmModule::new(find_part(dir, &part)?, name)?)
+            }
+            Binding::KvNamespace { name, namespace_id } => kv_namespaces.push(KvNamespace {
+                id: namespace_id,
+                binding: name,
+            }),
+            Binding::TextBlob { name, part } => {
+                let blob = fs::read_to_string(dir.join(part))?;
+                text_blobs.push(TextBlob::new(blob, name)?)
+            }
+            Binding::PlainText { name, text } => plain_texts.push(PlainText::new(name, text)?),
+        }
+    }
+
+    if let Some(main_module) = metadata.main_module {
+        let modules_dir = dir.join(MODULES_DIR_NAME);
+        let module_entries: Vec<ModuleEntry> =
+            serde_json::from_str(&fs::read_to_string(dir.join("modules.json"))?)?;
+
+        let mut modules = Vec::new();
+        for entry in module_entries {
+            let content = fs::read(modules_dir.join(&entry.name))?;
+            modules.push(Module::new(entry.name, entry.module_type, content));
+        }
+
+        return Ok(ProjectAssets::new_modules(
+            main_module.clone(),
+            modules_dir.join(main_module),
+            modules,
+            kv_namespaces,
+            plain_texts,
+        ));
+    }
+
+    let body_part = match metadata.body_part {
+        Some(body_part) => body_part,
+        None => failure::bail!(
+            "{} has neither a body part nor a main module",
+            dir.display()
+        ),
+    };
+
+    ProjectAssets::new(
+        find_part(dir, &body_part)?,
+        wasm_modules,
+        kv_namespaces,
+        text_blobs,
+        plain_texts,
+    )
+}
+
+// modules are written under their own directory, since their names may contain subdirectories
+const MODULES_DIR_NAME: &str = "modules";
+
+#[derive(Deserialize, Serialize)]
+struct ModuleEntry {
+    name: String,
+    #[serde(rename = "type")]
+    module_type: ModuleType,
+}
+
+fn write_module(modules_dir: &Path, name: &str, content: &[u8]) -> Result<(), failure::Error> {
+    let path = modules_dir.join(name);
+    if let Some(parent) = path.parent() {
+        fs::create_dir_all(parent)?;
+    }
+    fs::write(path, content)?;
+
+    Ok(())
+}
+
+// Script and wasm parts are named after the file stem, but written with their extension.
+fn find_part(dir: &Path, part: &str) -> Result<PathBuf, failure::Error> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.extension().is_some()
+            && !path.ends_with("metadata.json")
+            && filename_from_path(&path).as_deref() == Some(part)
+        {
+            return Ok(path);
+        }
+    }
+
+    failure::bail!("could not find the \"{}\" part in {}", part, dir.display())
+}
+
+fn part_file_name(path: &PathBuf) -> Result<PathBuf, failure::Error> {
+    match path.file_name() {
+        Some(file_name) => Ok(PathBuf::from(file_name)),
+        None => failure::bail!("filename should not be empty: {}", path.display()),
+    }
+}
+
+fn metadata(assets: &ProjectAssets) -> Metadata {
+    let (body_part, main_module) = match assets.format() {
+        ScriptFormat::ServiceWorker => (Some(assets.script_name()), None),
+        ScriptFormat::Modules => (None, Some(assets.script_name())),
+    };
+
+    Metadata {
+        body_part,
+        main_module,
         bindings: assets.bindings(),
-    });
+    }
+}
+
+fn add_metadata(mut form: Form, assets: &ProjectAssets) -> Result<Form, failure::Error> {
+    let metadata_json = serde_json::json!(&metadata(assets));
 
     let metadata = Part::text((metadata_json).to_string())
         .file_name("metadata.json")
@@ -217,3 +548,145 @@ fn concat_js(name: &str) -> Result<(), failure::Error> {
     fs::write("./worker/generated/script.js", js.as_bytes())?;
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn bindings_json(assets: &ProjectAssets) -> serde_json::Value {
+        serde_json::to_value(assets.bindings()).unwrap()
+    }
+
+    #[test]
+    fn it_reads_back_the_parts_of_a_service_worker() {
+        let dir = tempfile::tempdir().unwrap();
+        let script_path = dir.path().join("worker.js");
+        fs::write(&script_path, "addEventListener('fetch', () => {})").unwrap();
+        let assets = ProjectAssets::new(
+            script_path,
+            Vec::new(),
+            vec![KvNamespace {
+                id: "namespaceid".to_string(),
+                binding: "CACHE".to_string(),
+            }],
+            vec![TextBlob::new("{}".to_string(), STATIC_CONTENT_MANIFEST.to_string()).unwrap()],
+            vec![PlainText::new("ENV".to_string(), "production".to_string()).unwrap()],
+        )
+        .unwrap();
+
+        let parts_dir = dir.path().join("parts");
+        write_parts(&assets, &parts_dir).unwrap();
+        let read = read_parts(&parts_dir).unwrap();
+
+        assert_eq!(read.format(), ScriptFormat::ServiceWorker);
+        assert_eq!(read.script_name(), "worker");
+        assert_eq!(
+            fs::read_to_string(read.script_path()).unwrap(),
+            "addEventListener('fetch', () => {})"
+        );
+        assert_eq!(bindings_json(&read), bindings_json(&assets));
+        assert_eq!(read.text_blobs[0].data, "{}");
+    }
+
+    #[test]
+    fn it_reads_back_the_parts_of_a_modules_worker() {
+        let dir = tempfile::tempdir().unwrap();
+        let main_module_path = dir.path().join("index.mjs");
+        fs::write(&main_module_path, "export default {}").unwrap();
+        let assets = ProjectAssets::new_modules(
+            "index.mjs".to_string(),
+            main_module_path,
+            vec![Module::new(
+                "lib/util.mjs".to_string(),
+                ModuleType::ESModule,
+                b"export const util = 1".to_vec(),
+            )],
+            Vec::new(),
+            vec![PlainText::new("ENV".to_string(), "production".to_string()).unwrap()],
+        );
+
+        let parts_dir = dir.path().join("parts");
+        write_parts(&assets, &parts_dir).unwrap();
+        let read = read_parts(&parts_dir).unwrap();
+
+        assert_eq!(read.format(), ScriptFormat::Modules);
+        assert_eq!(read.script_name(), "index.mjs");
+        assert_eq!(
+            fs::read_to_string(read.script_path()).unwrap(),
+            "export default {}"
+        );
+        assert_eq!(read.modules.len(), 1);
+        assert_eq!(read.modules[0].name(), "lib/util.mjs");
+        assert_eq!(read.modules[0].module_type(), ModuleType::ESModule);
+        assert_eq!(read.modules[0].content(), b"export const util = 1");
+        assert_eq!(bindings_json(&read), bindings_json(&assets));
+    }
+
+    #[test]
+    fn it_rebinds_assets_to_the_target_and_keeps_the_site() {
+        let dir = tempfile::tempdir().unwrap();
+        let script_path = dir.path().join("worker.js");
+        fs::write(&script_path, "addEventListener('fetch', () => {})").unwrap();
+        let assets = ProjectAssets::new(
+            script_path.clone(),
+            Vec::new(),
+            vec![KvNamespace {
+                id: "stagingid".to_string(),
+                binding: "CACHE".to_string(),
+            }],
+            vec![
+                TextBlob::new("staging".to_string(), "CONFIG".to_string()).unwrap(),
+                TextBlob::new("{}".to_string(), STATIC_CONTENT_MANIFEST.to_string()).unwrap(),
+                TextBlob::new("{}".to_string(), STATIC_CONTENT_ASSETS.to_string()).unwrap(),
+            ],
+            vec![PlainText::new("ENV".to_string(), "staging".to_string()).unwrap()],
+        )
+        .unwrap();
+
+        let blob_path = dir.path().join("production.txt");
+        fs::write(&blob_path, "production").unwrap();
+        let mut vars = HashMap::new();
+        vars.insert("ENV".to_string(), "production".to_string());
+        let mut blobs = HashMap::new();
+        blobs.insert("PRODUCTION_CONFIG".to_string(), blob_path);
+        let target = Target {
+            account_id: "".to_string(),
+            kv_namespaces: vec![KvNamespace {
+                id: "productionid".to_string(),
+                binding: "CACHE".to_string(),
+            }],
+            name: "my-worker-production".to_string(),
+            target_type: TargetType::JavaScript,
+            webpack_config: None,
+            site: None,
+            vars: Some(vars),
+            text_blobs: Some(blobs),
+            build: None,
+        };
+
+        let rebound = rebind(assets, &target).unwrap();
+
+        assert_eq!(rebound.script_path(), script_path);
+        assert_eq!(rebound.kv_namespaces, target.kv_namespaces);
+        let plain_texts: Vec<(&str, &str)> = rebound
+            .plain_texts
+            .iter()
+            .map(|plain_text| (plain_text.name.as_str(), plain_text.value.as_str()))
+            .collect();
+        assert_eq!(plain_texts, vec![("ENV", "production")]);
+        let text_blobs: Vec<(&str, &str)> = rebound
+            .text_blobs
+            .iter()
+            .map(|blob| (blob.binding.as_str(), blob.data.as_str()))
+            .collect();
+        assert_eq!(
+            text_blobs,
+            vec![
+                ("PRODUCTION_CONFIG", "production"),
+                (STATIC_CONTENT_MANIFEST, "{}"),
+                (STATIC_CONTENT_ASSETS, "{}"),
+            ]
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::WalkBuilder;
+use reqwest::blocking::multipart::Part;
+
+use crate::settings::toml::{ModuleRule, ModuleType};
+
+#[derive(Debug)]
+pub struct Module {
+    name: String,
+    module_type: ModuleType,
+    content: Vec<u8>,
+}
+
+impl Module {
+    pub fn new(name: String, module_type: ModuleType, content: Vec<u8>) -> Self {
+        Self {
+            name,
+            module_type,
+            content,
+        }
+    }
+
+    // `name` is the path of the module relative to the modules directory, which is
+    // what other modules import it by
+    pub fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn module_type(&self) -> ModuleType {
+        self.module_type
+    }
+
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+
+    pub fn part(&self) -> Result<Part, failure::Error> {
+        let part = Part::bytes(self.content.clone())
+            .file_name(self.name.clone())
+            .mime_str(self.module_type.content_type())?;
+
+        Ok(part)
+    }
+}
+
+struct Matcher {
+    module_type: ModuleType,
+    globs: Override,
+}
+
+// Finds the main module and every other module in `dir` that matches one of `rules`
+// (followed by the default rules). Files that no rule matches are not uploaded.
+pub fn collect(
+    dir: &Path,
+    main: &Path,
+    rules: &[ModuleRule],
+) -> Result<(String, PathBuf, Vec<Module>), failure::Error> {
+    let matchers = matchers(dir, rules)?;
+
+    let main_path = dir.join(main);
+    if !main_path.is_file() {
+        failure::bail!("main module {} does not exist", main_path.display())
+    }
+    let main_name = module_name(dir, &main_path)?;
+    if module_type(&matchers, &main_path) != Some(ModuleType::ESModule) {
+        failure::bail!(
+            "the main module {} must be an ES module. Give it a .mjs extension, or add a rule with type = \"ESModule\" that matches it",
+            main_name
+        )
+    }
+
+    let mut modules = Vec::new();
+    for entry in WalkBuilder::new(dir).standard_filters(false).build() {
+        let entry = entry?;
+        let path = entry.path();
+        if !path.is_file() || path == main_path {
+            continue;
+        }
+
+        if let Some(module_type) = module_type(&matchers, path) {
+            log::info!("found {:?} module {}", module_type, path.display());
+            modules.push(Module::new(
+                module_name(dir, path)?,
+                module_type,
+                fs::read(path)?,
+            ));
+        }
+    }
+    modules.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok((main_name, main_path, modules))
+}
+
+fn matchers(dir: &Path, rules: &[ModuleRule]) -> Result<Vec<Matcher>, failure::Error> {
+    let default_rules = ModuleRule::defaults();
+    let mut matchers = Vec::new();
+    let mut completed_types = Vec::new();
+
+    for rule in rules.iter().chain(default_rules.iter()) {
+        if completed_types.contains(&rule.module_type) {
+            continue;
+        }
+
+        let mut globs = OverrideBuilder::new(dir);
+        for glob in &rule.globs {
+            globs.add(glob)?;
+        }
+        matchers.push(Matcher {
+            module_type: rule.module_type,
+            globs: globs.build()?,
+        });
+
+        if !rule.fallthrough {
+            completed_types.push(rule.module_type);
+        }
+    }
+
+    Ok(matchers)
+}
+
+fn module_type(matchers: &[Matcher], path: &Path) -> Option<ModuleType> {
+    matchers
+        .iter()
+        .find(|matcher| matcher.globs.matched(path, false).is_whitelist())
+        .map(|matcher| matcher.module_type)
+}
+
+fn module_name(dir: &Path, path: &Path) -> Result<String, failure::Error> {
+    let relative_path = path.strip_prefix(dir)?;
+    let components = relative_path
+        .components()
+        .map(|component| component.as_os_str().to_str())
+        .collect::<Option<Vec<_>>>();
+
+    match components {
+        Some(components) => Ok(components.join("/")),
+        None => failure::bail!("module path {} is not valid unicode", path.display()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn modules_dir(files: &[&str]) -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        for file in files {
+            let path = dir.path().join(file);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(path, "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn it_collects_modules_with_the_default_rules() {
+        let dir = modules_dir(&["index.mjs", "lib/util.mjs", "legacy.js", "README.md"]);
+
+        let (main_name, _, modules) = collect(dir.path(), Path::new("index.mjs"), &[]).unwrap();
+
+        assert_eq!(main_name, "index.mjs");
+        let modules: Vec<(String, ModuleType)> = modules
+            .iter()
+            .map(|m| (m.name(), m.module_type()))
+            .collect();
+        assert_eq!(
+            modules,
+            vec![
+                ("legacy.js".to_string(), ModuleType::CommonJS),
+                ("lib/util.mjs".to_string(), ModuleType::ESModule),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_applies_rules_before_the_defaults() {
+        let dir = modules_dir(&["index.js", "data/page.html", "add.wasm"]);
+        let rules = vec![
+            ModuleRule {
+                globs: vec!["**/*.js".to_string()],
+                module_type: ModuleType::ESModule,
+                fallthrough: false,
+            },
+            ModuleRule {
+                globs: vec!["**/*.html".to_string()],
+                module_type: ModuleType::Text,
+                fallthrough: false,
+            },
+            ModuleRule {
+                globs: vec!["**/*.wasm".to_string()],
+                module_type: ModuleType::CompiledWasm,
+                fallthrough: false,
+            },
+        ];
+
+        let (_, _, modules) = collect(dir.path(), Path::new("index.js"), &rules).unwrap();
+
+        let modules: Vec<(String, ModuleType)> = modules
+            .iter()
+            .map(|m| (m.name(), m.module_type()))
+            .collect();
+        assert_eq!(
+            modules,
+            vec![
+                ("add.wasm".to_string(), ModuleType::CompiledWasm),
+                ("data/page.html".to_string(), ModuleType::Text),
+            ]
+        );
+    }
+
+    #[test]
+    fn it_requires_the_main_module_to_be_an_es_module() {
+        let dir = modules_dir(&["index.js"]);
+
+        assert!(collect(dir.path(), Path::new("index.js"), &[]).is_err());
+        assert!(collect(dir.path(), Path::new("missing.mjs"), &[]).is_err());
+    }
+}
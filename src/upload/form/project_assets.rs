@@ -4,16 +4,26 @@ use failure::format_err;
 
 use super::binding::Binding;
 use super::filename_from_path;
+use super::modules_worker::Module;
 use super::plain_text::PlainText;
 use super::text_blob::TextBlob;
 use super::wasm_module::WasmModule;
 
 use crate::settings::toml::KvNamespace;
 
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum ScriptFormat {
+    ServiceWorker,
+    // the script is the main module, and imports the other modules
+    Modules,
+}
+
 #[derive(Debug)]
 pub struct ProjectAssets {
     script_name: String,
     script_path: PathBuf,
+    format: ScriptFormat,
+    pub modules: Vec<Module>,
     pub wasm_modules: Vec<WasmModule>,
     pub kv_namespaces: Vec<KvNamespace>,
     pub text_blobs: Vec<TextBlob>,
@@ -35,6 +45,8 @@ impl ProjectAssets {
         Ok(Self {
             script_name,
             script_path,
+            format: ScriptFormat::ServiceWorker,
+            modules: Vec::new(),
             wasm_modules,
             kv_namespaces,
             text_blobs,
@@ -42,6 +54,25 @@ impl ProjectAssets {
         })
     }
 
+    pub fn new_modules(
+        main_module_name: String,
+        main_module_path: PathBuf,
+        modules: Vec<Module>,
+        kv_namespaces: Vec<KvNamespace>,
+        plain_texts: Vec<PlainText>,
+    ) -> Self {
+        Self {
+            script_name: main_module_name,
+            script_path: main_module_path,
+            format: ScriptFormat::Modules,
+            modules,
+            wasm_modules: Vec::new(),
+            kv_namespaces,
+            text_blobs: Vec::new(),
+            plain_texts,
+        }
+    }
+
     pub fn bindings(&self) -> Vec<Binding> {
         let mut bindings = Vec::new();
 
@@ -72,4 +103,8 @@ impl ProjectAssets {
     pub fn script_path(&self) -> PathBuf {
         self.script_path.clone()
     }
+
+    pub fn format(&self) -> ScriptFormat {
+        self.format
+    }
 }
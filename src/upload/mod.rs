@@ -7,19 +7,19 @@ pub use package::Package;
 use reqwest::blocking::Client;
 
 use crate::settings::toml::Target;
-use crate::sites::AssetManifest;
+use form::ProjectAssets;
 
 pub fn script(
     client: &Client,
     target: &Target,
-    asset_manifest: Option<AssetManifest>,
+    assets: &ProjectAssets,
 ) -> Result<(), failure::Error> {
     let worker_addr = format!(
         "https://api.cloudflare.com/client/v4/accounts/{}/workers/scripts/{}",
         target.account_id, target.name,
     );
 
-    let script_upload_form = form::build(target, asset_manifest, None)?;
+    let script_upload_form = form::build_form(assets, None)?;
 
     let res = client
         .put(&worker_addr)
@@ -4,7 +4,7 @@ use ignore::WalkBuilder;
 pub use watcher::wait_for_changes;
 
 use crate::build::command;
-use crate::settings::toml::{Target, TargetType};
+use crate::settings::toml::{Builder, Target, TargetType};
 use crate::terminal::message::{Message, StdOut};
 use crate::wranglerjs;
 use crate::{commands, install};
@@ -27,6 +27,10 @@ pub fn watch_and_build(
     target: &Target,
     tx: Option<mpsc::Sender<()>>,
 ) -> Result<(), failure::Error> {
+    if let Some(builder) = &target.build {
+        return watch_custom_build(builder.clone(), tx);
+    }
+
     let target_type = &target.target_type;
     match target_type {
         TargetType::JavaScript => {
@@ -108,3 +112,48 @@ pub fn watch_and_build(
 
     Ok(())
 }
+
+// watch the `[build]` watch_dir and re-run the build command on changes
+fn watch_custom_build(
+    builder: Builder,
+    tx: Option<mpsc::Sender<()>>,
+) -> Result<(), failure::Error> {
+    let watch_dir = builder.watch_dir.clone();
+    if !watch_dir.is_dir() {
+        failure::bail!(
+            "watch_dir {} does not exist. Set watch_dir in the [build] section of your wrangler.toml to the directory your source files are in",
+            watch_dir.display()
+        )
+    }
+
+    thread::spawn(move || {
+        let (watcher_tx, watcher_rx) = mpsc::channel();
+        let mut watcher = notify::watcher(watcher_tx, Duration::from_secs(1)).unwrap();
+
+        watcher.watch(&watch_dir, RecursiveMode::Recursive).unwrap();
+        StdOut::info(&format!("watching {:?}", &watch_dir));
+
+        loop {
+            match wait_for_changes(&watcher_rx, COOLDOWN_PERIOD) {
+                Ok(_path) => {
+                    if let Some((command_str, command)) = builder.build_command() {
+                        StdOut::working(&format!("Running {}", command_str));
+                        if let Err(e) = commands::run(command, command_str) {
+                            StdOut::user_error(&e.to_string());
+                            continue;
+                        }
+                    }
+                    if let Some(tx) = tx.clone() {
+                        tx.send(()).expect("--watch change message failed to send");
+                    }
+                }
+                Err(e) => {
+                    log::debug!("{:?}", e);
+                    StdOut::user_error("Something went wrong while watching.")
+                }
+            }
+        }
+    });
+
+    Ok(())
+}